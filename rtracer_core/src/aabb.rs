@@ -28,6 +28,16 @@ impl Aabb {
         Aabb::new(Vec3::from_element(max), Vec3::from_element(min))
     }
 
+    // bounds of unbounded primitives like planes
+    pub fn infinite() -> Aabb {
+        let inf = std::f32::INFINITY;
+        Aabb::new(Vec3::from_element(-inf), Vec3::from_element(inf))
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.min.iter().chain(self.max.iter()).all(|c| c.is_finite())
+    }
+
     pub fn center(&self) -> Vec3 {
         (self.min + self.max) / 2.
     }
@@ -36,10 +46,23 @@ impl Aabb {
         self.max - self.min
     }
 
+    pub fn surface_area(&self) -> f32 {
+        let size = self.size();
+        2. * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
     pub fn normal_at(&self, point: &Vec3) -> Vec3 {
         aabb_noraml_at(self, point)
     }
 
+    // unlike intersect also true when the ray starts inside and leaves after t_max
+    pub fn overlaps_ray(&self, ray: &Ray, (t_min, t_max): (f32, f32)) -> bool {
+        match ray_aabb_intersection(ray, self) {
+            Some((near, far)) => near < t_max && far > t_min,
+            None => false,
+        }
+    }
+
     pub fn add_point(&mut self, p: &Vec3) {
         if p.x < self.min.x {
             self.min.x = p.x;
@@ -136,24 +159,35 @@ mod tests {
     fn test_normal_at_simple() {
         let pos = Vec3::new(3., 2., -7.);
 
-        let aabb = Aabb::from_center_size(pos, 2. * Vec3::unit());
+        let aabb = Aabb::from_center_size(pos, 2. * Vec3::repeat(1.));
 
-        assert_eq!(aabb.normal_at(&(pos + Vec3::new_x())), Vec3::new_x());
-        assert_eq!(aabb.normal_at(&(pos -Vec3::new_x())), -Vec3::new_x());
+        assert_eq!(aabb.normal_at(&(pos + Vec3::x())), Vec3::x());
+        assert_eq!(aabb.normal_at(&(pos -Vec3::x())), -Vec3::x());
 
-        assert_eq!(aabb.normal_at(&(pos + Vec3::new_y())), Vec3::new_y());
-        assert_eq!(aabb.normal_at(&(pos - Vec3::new_y())), -Vec3::new_y());
+        assert_eq!(aabb.normal_at(&(pos + Vec3::y())), Vec3::y());
+        assert_eq!(aabb.normal_at(&(pos - Vec3::y())), -Vec3::y());
 
-        assert_eq!(aabb.normal_at(&(pos + Vec3::new_z())), Vec3::new_z());
-        assert_eq!(aabb.normal_at(&(pos - Vec3::new_z())), -Vec3::new_z());
+        assert_eq!(aabb.normal_at(&(pos + Vec3::z())), Vec3::z());
+        assert_eq!(aabb.normal_at(&(pos - Vec3::z())), -Vec3::z());
     }
 
     #[test]
     fn test_normal_at_hard() {
-        let aabb = Aabb::from_center_size(Vec3::origin(), 2. * Vec3::unit());
+        let aabb = Aabb::from_center_size(Vec3::zeros(), 2. * Vec3::repeat(1.));
 
-        assert_eq!(aabb.normal_at(&Vec3::new(1., 0.4, -0.2)), Vec3::new_x());
-        assert_eq!(aabb.normal_at(&Vec3::new(-1., 0.97, 0.89)), -Vec3::new_x());
-        assert_eq!(aabb.normal_at(&Vec3::new(1., -0.97, 0.89)), Vec3::new_x());
+        assert_eq!(aabb.normal_at(&Vec3::new(1., 0.4, -0.2)), Vec3::x());
+        assert_eq!(aabb.normal_at(&Vec3::new(-1., 0.97, 0.89)), -Vec3::x());
+        assert_eq!(aabb.normal_at(&Vec3::new(1., -0.97, 0.89)), Vec3::x());
+    }
+
+    #[test]
+    fn test_infinite() {
+        let infinite = Aabb::infinite();
+        assert!(!infinite.is_finite());
+        assert!(Aabb::from_center_size(Vec3::zeros(), Vec3::repeat(1.)).is_finite());
+        assert!(!Aabb::union(&infinite, &Aabb::from_center_size(Vec3::zeros(), Vec3::repeat(1.))).is_finite());
+
+        let ray = Ray::new(Vec3::new(1., 2., 3.), Vec3::new(0., 0., -1.));
+        assert!(infinite.overlaps_ray(&ray, (0., 1.)));
     }
 }
@@ -1,78 +1,324 @@
-use crate::prelude::*;
-use rand::Rng;
+use std::ops::Range;
+
+use crate::aabb::Aabb;
 use crate::bounded::Bounded;
 
-pub enum BvhNodeData<H: Bounded + Copy + Clone> {
-    Leaf(H),
+const TRAVERSAL_COST: f32 = 1.;
+const INTERSECTION_COST: f32 = 1.;
+
+pub enum BvhBuildNode {
+    Leaf {
+        aabb: Aabb,
+        // range into the reordered objects slice
+        objs: Range<usize>,
+    },
     Node {
-        left: Box<BvhNode<H>>,
-        right: Box<BvhNode<H>>,
+        aabb: Aabb,
+        left: Box<BvhBuildNode>,
+        right: Box<BvhBuildNode>,
     },
 }
 
-pub struct BvhNode<H: Hit + Copy + Clone> {
-    data: BvhNodeData<H>,
-    aabb: Aabb,
+impl BvhBuildNode {
+    pub fn aabb(&self) -> Aabb {
+        match self {
+            BvhBuildNode::Leaf { aabb, .. } => *aabb,
+            BvhBuildNode::Node { aabb, .. } => *aabb,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BvhBuilder {
+    bins: usize,
+    max_leaf_size: usize,
 }
 
-impl<H: Hit + Copy + Clone> BvhNode<H> {
-    pub fn build(objs: &mut [H]) -> BvhNode<H> {
-        let sort_axis = rand::thread_rng().gen_range(0, 3);
-        objs.sort_by(|a, b| a.aabb().min[sort_axis].partial_cmp(&b.aabb().min[sort_axis]).unwrap());
+impl BvhBuilder {
+    pub fn new(bins: usize, max_leaf_size: usize) -> BvhBuilder {
+        assert!(bins >= 2, "sah builder need at least two bins");
+        assert!(max_leaf_size >= 1, "leaf must contain at least one object");
+        BvhBuilder { bins, max_leaf_size }
+    }
+
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+
+    pub fn max_leaf_size(&self) -> usize {
+        self.max_leaf_size
+    }
+
+    // reorders objs so every leaf refers to a contiguous range of it,
+    // unbounded objs go last into one leaf next to the tree of bounded ones
+    pub fn build<T: Bounded>(&self, objs: &mut [T]) -> BvhBuildNode {
+        assert!(!objs.is_empty(), "cant build bvh from zero objects");
+
+        let mut items: Vec<(Aabb, usize)> = objs.iter().enumerate()
+            .map(|(idx, o)| (o.aabb(), idx))
+            .collect();
+
+        let n_bounded = partition(&mut items, |(aabb, _)| aabb.is_finite());
+        let unbounded = BvhBuildNode::Leaf { aabb: Aabb::infinite(), objs: n_bounded..items.len() };
+
+        let root = match n_bounded {
+            0 => unbounded,
+            n if n == items.len() => self.build_recursive(&mut items, 0),
+            n => {
+                let left = Box::new(self.build_recursive(&mut items[..n], 0));
+                BvhBuildNode::Node { aabb: Aabb::infinite(), left, right: Box::new(unbounded) }
+            },
+        };
+
+        apply_permutation(objs, items.iter().map(|(_, idx)| *idx).collect());
+
+        root
+    }
+
+    fn build_recursive(&self, items: &mut [(Aabb, usize)], offset: usize) -> BvhBuildNode {
+        let n = items.len();
 
-        let n = objs.len();
-        assert!(n != 0, "cant build bvh from zero objects");
+        let aabb = items.iter().fold(Aabb::empty(), |mut aabb, (item, _)| { aabb.add_aabb(item); aabb });
 
+        let leaf = BvhBuildNode::Leaf { aabb, objs: offset..offset + n };
         if n == 1 {
-            BvhNode { data: BvhNodeData::Leaf(objs[0]), aabb: objs[0].aabb() }
-        } else {
-            let (mut l_objs, mut r_objs) = objs.split_at_mut(n / 2);
+            return leaf;
+        }
+
+        let mid = match self.find_split(items, &aabb) {
+            Some(split) if n > self.max_leaf_size || split.cost < INTERSECTION_COST * n as f32 => {
+                partition(items, |(item, _)| split.bin_of(&item.center()) < split.bin)
+            },
+            // all centroids at one point, nothing to separate by sah
+            None if n > self.max_leaf_size => n / 2,
+            _ => return leaf,
+        };
+
+        let (l_items, r_items) = items.split_at_mut(mid);
+        let left = Box::new(self.build_recursive(l_items, offset));
+        let right = Box::new(self.build_recursive(r_items, offset + mid));
+
+        BvhBuildNode::Node { aabb, left, right }
+    }
+
+    fn find_split(&self, items: &[(Aabb, usize)], aabb: &Aabb) -> Option<Split> {
+        let centroids = items.iter().fold(Aabb::empty(), |mut aabb, (item, _)| { aabb.add_point(&item.center()); aabb });
+        let parent_area = aabb.surface_area();
+
+        let mut best: Option<Split> = None;
+
+        for axis in 0..3 {
+            let (min, extent) = (centroids.min[axis], centroids.size()[axis]);
+            if extent <= 0. {
+                continue;
+            }
+
+            let mut counts = vec![0usize; self.bins];
+            let mut bounds = vec![Aabb::empty(); self.bins];
+
+            let split = Split { axis, bin: 0, min, extent, bins: self.bins, cost: 0. };
+            for (item, _) in items {
+                let bin = split.bin_of(&item.center());
+                counts[bin] += 1;
+                bounds[bin].add_aabb(item);
+            }
+
+            // right_costs[i] - area * count of bins [i, bins)
+            let mut right_costs = vec![0.; self.bins];
+            let mut right = (Aabb::empty(), 0);
+            for bin in (1..self.bins).rev() {
+                right.0.add_aabb(&bounds[bin]);
+                right.1 += counts[bin];
+                right_costs[bin] = area_cost(&right.0, right.1);
+            }
+
+            let mut left = (Aabb::empty(), 0);
+            for bin in 1..self.bins {
+                left.0.add_aabb(&bounds[bin - 1]);
+                left.1 += counts[bin - 1];
+
+                if left.1 == 0 || left.1 == items.len() {
+                    continue;
+                }
+
+                let cost = TRAVERSAL_COST
+                    + INTERSECTION_COST * (area_cost(&left.0, left.1) + right_costs[bin]) / parent_area;
+
+                if best.as_ref().map_or(true, |best| cost < best.cost) {
+                    best = Some(Split { bin, cost, ..split });
+                }
+            }
+        }
 
-            let left = Box::new(BvhNode::build(&mut l_objs));
-            let right = Box::new(BvhNode::build(&mut r_objs));
-            let aabb = Aabb::union(&left.aabb(), &right.aabb());
+        best
+    }
+}
+
+impl Default for BvhBuilder {
+    fn default() -> BvhBuilder {
+        BvhBuilder::new(16, 4)
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Split {
+    axis: usize,
+    bin: usize,
+    min: f32,
+    extent: f32,
+    bins: usize,
+    cost: f32,
+}
+
+impl Split {
+    fn bin_of(&self, centroid: &crate::Vec3) -> usize {
+        let bin = (self.bins as f32 * (centroid[self.axis] - self.min) / self.extent) as usize;
+        bin.min(self.bins - 1)
+    }
+}
 
-            let data = BvhNodeData::Node { left, right };
-            BvhNode { data, aabb }
+fn area_cost(aabb: &Aabb, count: usize) -> f32 {
+    if count == 0 {
+        0.
+    } else {
+        aabb.surface_area() * count as f32
+    }
+}
+
+fn partition<T, F: Fn(&T) -> bool>(items: &mut [T], pred: F) -> usize {
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
         }
     }
+    mid
 }
 
-impl<H: Hit + Copy + Clone> Hit for BvhNode<H> {
-    fn hit(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<HitRecord> {
-        if self.aabb().intersect(ray, t_min_max).is_none() {
-            return None
+// moves objs[order[i]] to position i
+fn apply_permutation<T>(objs: &mut [T], mut order: Vec<usize>) {
+    for i in 0..objs.len() {
+        let mut cur = i;
+        loop {
+            let src = order[cur];
+            order[cur] = cur;
+            if src == i || src == cur {
+                break;
+            }
+            objs.swap(cur, src);
+            cur = src;
         }
+    }
+}
 
-        match &self.data {
-            BvhNodeData::Leaf(leaf) => {
-                leaf.hit(ray, t_min_max)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vec3;
+
+    fn leaves(node: &BvhBuildNode, out: &mut Vec<Range<usize>>) {
+        match node {
+            BvhBuildNode::Leaf { objs, .. } => out.push(objs.clone()),
+            BvhBuildNode::Node { left, right, .. } => {
+                leaves(left, out);
+                leaves(right, out);
             },
-            BvhNodeData::Node { left, right} => {
-                let l_hit = left.hit(ray, t_min_max);
-                let r_hit = right.hit(ray, t_min_max);
-
-                match (l_hit, r_hit) {
-                    (Some(l_hit), Some(r_hit)) => {
-                        if l_hit.t < r_hit.t {
-                            Some(l_hit)
-                        } else {
-                            Some(r_hit)
-                        }
-                    },
-                    (Some(l_hit), None) => {
-                        Some(l_hit)
-                    },
-                    (None, Some(r_hit)) => {
-                        Some(r_hit)
-                    },
-                    _ => None
+        }
+    }
+
+    fn boxes(n: usize) -> Vec<Aabb> {
+        (0..n)
+            .map(|i| {
+                let i = ((i * 7919) % n) as f32;
+                Aabb::from_center_size(Vec3::new(i, (i * 0.37).sin(), 0.), Vec3::repeat(0.5))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_permutation() {
+        let mut objs = vec!['a', 'b', 'c', 'd', 'e'];
+        apply_permutation(&mut objs, vec![3, 0, 4, 1, 2]);
+        assert_eq!(objs, vec!['d', 'a', 'e', 'b', 'c']);
+    }
+
+    #[test]
+    fn test_leaves_cover_all_objects() {
+        let mut objs = boxes(100);
+        let builder = BvhBuilder::new(8, 3);
+        let root = builder.build(&mut objs);
+
+        let mut ranges = vec![];
+        leaves(&root, &mut ranges);
+
+        let mut next = 0;
+        for range in ranges {
+            assert_eq!(range.start, next);
+            assert!(range.len() >= 1 && range.len() <= builder.max_leaf_size());
+            next = range.end;
+        }
+        assert_eq!(next, objs.len());
+    }
+
+    #[test]
+    fn test_leaf_bounds_contain_objects() {
+        let mut objs = boxes(50);
+        let root = BvhBuilder::default().build(&mut objs);
+
+        let bounds = root.aabb();
+        for o in &objs {
+            for a in 0..3 {
+                assert!(bounds.min[a] <= o.min[a] && o.max[a] <= bounds.max[a]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_is_deterministic() {
+        let mut a = boxes(64);
+        let mut b = boxes(64);
+        BvhBuilder::default().build(&mut a);
+        BvhBuilder::default().build(&mut b);
+
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!(a.min, b.min);
+        }
+    }
+
+    #[test]
+    fn test_unbounded_objects() {
+        let mut objs = boxes(20);
+        objs.insert(7, Aabb::infinite());
+        objs.push(Aabb::infinite());
+        let root = BvhBuilder::default().build(&mut objs);
+
+        assert!(objs[..20].iter().all(Aabb::is_finite));
+        match &root {
+            BvhBuildNode::Node { left, right, .. } => {
+                assert!(left.aabb().is_finite());
+                match right.as_ref() {
+                    BvhBuildNode::Leaf { objs: range, .. } => assert_eq!(*range, 20..22),
+                    _ => panic!("unbounded objects must be in one leaf"),
                 }
             },
+            _ => panic!("root must split bounded and unbounded objects"),
+        }
+
+        let mut planes = vec![Aabb::infinite(); 3];
+        match BvhBuilder::default().build(&mut planes) {
+            BvhBuildNode::Leaf { objs: range, .. } => assert_eq!(range, 0..3),
+            _ => panic!("only unbounded objects must give one leaf"),
         }
     }
 
-    fn aabb(&self) -> Aabb {
-        self.aabb
+    #[test]
+    fn test_coincident_centroids() {
+        let mut objs = vec![Aabb::from_center_size(Vec3::zeros(), Vec3::repeat(1.)); 10];
+        let root = BvhBuilder::new(4, 2).build(&mut objs);
+
+        let mut ranges = vec![];
+        leaves(&root, &mut ranges);
+        assert!(ranges.iter().all(|r| r.len() <= 2));
     }
 }
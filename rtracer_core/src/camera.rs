@@ -1,38 +1,120 @@
+use std::f32::consts::PI;
+
+use serde::{Serialize, Deserialize};
+
 use crate::{Vec3, Vec2};
 use crate::ray::Ray;
 use na::{Rotation3, Unit};
 
 use winit::{Event, WindowEvent, ElementState, VirtualKeyCode};
 
+// how image points map to rays
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Projection {
+    // pinhole or thin lens with camera vfov
+    Perspective,
+    // parallel rays, height of the view in world units
+    Orthographic { height: f32 },
+    // equidistant fisheye, fov in degrees covers image height
+    Fisheye { fov: f32 },
+    // full sphere around the camera, image center looks forward
+    Equirectangular,
+}
+
+impl Default for Projection {
+    fn default() -> Projection {
+        Projection::Perspective
+    }
+}
+
+// perspective image plane is placed at focus distance, so rays from any lens point meet there
+// orthographic image plane goes through the camera
 pub struct RaycastCamera {
     pub origin: Vec3,
     pub upper_left: Vec3,
     pub horizontal: Vec3,
     pub vertical: Vec3,
+    // camera basis, right, up and backward
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+    pub aspect: f32,
+    pub lens_radius: f32,
+    pub projection: Projection,
+    pub shutter: (f32, f32),
+    pub motion: Vec3,
 }
 
 impl RaycastCamera {
     pub fn from_camera(camera: &Camera) -> RaycastCamera {
-        let theta = camera.vfov.to_radians();
-        let half_height = (theta / 2.).tan();
-        let half_width = camera.aspect * half_height;
-
         let w = (camera.lookfrom - camera.lookat).normalize();
         let u = (camera.vup.cross(&w)).normalize();
         let v = (w.cross(&u)).normalize();
 
+        let (half_height, plane) = match camera.projection {
+            Projection::Orthographic { height } => (height / 2., Vec3::zeros()),
+            _ => {
+                let focus = camera.focus_distance;
+                (focus * (camera.vfov.to_radians() / 2.).tan(), -focus * w)
+            },
+        };
+        let half_width = camera.aspect * half_height;
+
         let origin = camera.lookfrom;
-        let upper_left = origin - half_width * u + half_height * v - w;
+        let upper_left = origin + plane - half_width * u + half_height * v;
         let horizontal = 2. * half_width * u;
         let vertical = -2. * half_height * v;
 
-        RaycastCamera { origin, upper_left, horizontal, vertical }
+        RaycastCamera { origin, upper_left, horizontal, vertical, u, v, w, aspect: camera.aspect,
+                        lens_radius: camera.aperture / 2., projection: camera.projection,
+                        shutter: camera.shutter, motion: camera.motion }
+    }
+
+    // u in [0, 1) maps to moment between shutter open and close
+    pub fn shutter_time(&self, u: f32) -> f32 {
+        self.shutter.0 + (self.shutter.1 - self.shutter.0) * u
     }
 
-    pub fn get_ray(&self, (u, v): (f32, f32)) -> Ray {
+    // lens is a point in unit disk, (0, 0) gives pinhole ray, only perspective projection uses it
+    // None for points outside of fisheye circle
+    pub fn get_ray(&self, uv: (f32, f32), lens: (f32, f32), time: f32) -> Option<Ray> {
+        self.get_static_ray(uv, lens)
+            .map(|ray| Ray::with_time(ray.origin + self.motion * time, ray.direction, time))
+    }
+
+    fn get_static_ray(&self, (u, v): (f32, f32), (lens_x, lens_y): (f32, f32)) -> Option<Ray> {
         debug_assert!(u >= 0f32 && u < 1.05f32);
         debug_assert!(v >= 0f32 && v < 1.05f32);
-        Ray::new(self.origin, self.upper_left + u * self.horizontal + v * self.vertical - self.origin)
+
+        match self.projection {
+            Projection::Perspective => {
+                let origin = self.origin + self.lens_radius * (lens_x * self.u + lens_y * self.v);
+                Some(Ray::new(origin, self.upper_left + u * self.horizontal + v * self.vertical - origin))
+            },
+            Projection::Orthographic { .. } => {
+                Some(Ray::new(self.upper_left + u * self.horizontal + v * self.vertical, -self.w))
+            },
+            Projection::Fisheye { fov } => {
+                // image point in units of half height
+                let (x, y) = ((2. * u - 1.) * self.aspect, 1. - 2. * v);
+                let r = (x * x + y * y).sqrt();
+                let theta = r * fov.to_radians() / 2.;
+                if theta > PI {
+                    return None
+                }
+
+                let (x, y) = if r > 0. { (x / r, y / r) } else { (0., 0.) };
+                let direction = theta.sin() * (x * self.u + y * self.v) - theta.cos() * self.w;
+                Some(Ray::new(self.origin, direction))
+            },
+            Projection::Equirectangular => {
+                let phi = 2. * PI * (u - 0.5);
+                let theta = PI * v;
+                let direction = theta.sin() * phi.sin() * self.u + theta.cos() * self.v - theta.sin() * phi.cos() * self.w;
+                Some(Ray::new(self.origin, direction))
+            },
+        }
     }
 }
 
@@ -42,6 +124,15 @@ pub struct Camera {
     pub vup: Unit<Vec3>,
     pub vfov: f32,
     pub aspect: f32,
+    // lens diameter, zero for pinhole camera
+    pub aperture: f32,
+    // distance to the plane in focus
+    pub focus_distance: f32,
+    pub projection: Projection,
+    // open and close time within [0, 1], moving objects are at their keyframes at 0 and 1
+    pub shutter: (f32, f32),
+    // camera translation at time 1
+    pub motion: Vec3,
     position: Option<Vec2>,
     forward_enable: bool,
     backward_enable: bool,
@@ -52,10 +143,22 @@ pub struct Camera {
 impl Camera {
     pub fn new(lookfrom: Vec3, lookat: Vec3, vup: Vec3, vfov: f32, aspect: f32) -> Camera {
         Camera { lookfrom, lookat, vup: Unit::new_normalize(vup), vfov, aspect,
-            position: None,
+            aperture: 0., focus_distance: (lookat - lookfrom).norm(), projection: Projection::default(),
+            shutter: (0., 0.), motion: Vec3::zeros(), position: None,
             forward_enable: false, backward_enable: false, left_enable: false, right_enable: false }
     }
 
+    pub fn with_lens(mut self, aperture: f32, focus_distance: f32) -> Camera {
+        self.aperture = aperture;
+        self.focus_distance = focus_distance;
+        self
+    }
+
+    // ray through center of the image from center of the lens
+    pub fn center_ray(&self) -> Ray {
+        Ray::new(self.lookfrom, self.lookat - self.lookfrom)
+    }
+
     pub fn translate(&mut self, trans: &Vec3) {
         self.lookfrom += trans;
         self.lookat += trans;
@@ -152,4 +255,74 @@ impl Camera {
             },
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lens_rays_meet_at_focus() {
+        let camera = Camera::new(Vec3::zeros(), -Vec3::z(), Vec3::y(), 60., 2.).with_lens(0.5, 4.);
+        let raycast_camera = RaycastCamera::from_camera(&camera);
+
+        for &uv in &[(0.5, 0.5), (0.1, 0.8), (0.9, 0.2)] {
+            let pinhole = raycast_camera.get_ray(uv, (0., 0.), 0.).unwrap();
+            let focus_point = pinhole.point_at_parameter(4. / -pinhole.direction.z);
+
+            for &lens in &[(1., 0.), (-0.5, 0.5), (0., -1.)] {
+                let ray = raycast_camera.get_ray(uv, lens, 0.).unwrap();
+                assert!(ray.origin != pinhole.origin);
+                let point = ray.point_at_parameter((4. + ray.origin.z) / -ray.direction.z);
+                assert!((point - focus_point).norm() < 1e-4);
+            }
+        }
+
+        // pinhole camera keeps every ray in the origin
+        let pinhole = RaycastCamera::from_camera(&Camera::new(Vec3::zeros(), -Vec3::z(), Vec3::y(), 60., 2.));
+        assert_eq!(pinhole.get_ray((0.3, 0.3), (1., 0.), 0.).unwrap().origin, Vec3::zeros());
+    }
+
+    #[test]
+    fn test_projections() {
+        let mut camera = Camera::new(Vec3::new(0., 0., 2.), Vec3::zeros(), Vec3::y(), 60., 2.);
+
+        camera.projection = Projection::Orthographic { height: 4. };
+        let raycast_camera = RaycastCamera::from_camera(&camera);
+        let ray = raycast_camera.get_ray((0., 0.), (0.5, 0.5), 0.).unwrap();
+        assert!((ray.origin - Vec3::new(-4., 2., 2.)).norm() < 1e-5);
+        assert!((ray.direction + Vec3::z()).norm() < 1e-5);
+
+        camera.projection = Projection::Fisheye { fov: 180. };
+        let raycast_camera = RaycastCamera::from_camera(&camera);
+        let center = raycast_camera.get_ray((0.5, 0.5), (0., 0.), 0.).unwrap();
+        assert!((center.direction + Vec3::z()).norm() < 1e-5);
+        // top edge of the image is 90 degrees away from view direction
+        let top = raycast_camera.get_ray((0.5, 0.), (0., 0.), 0.).unwrap();
+        assert!((top.direction - Vec3::y()).norm() < 1e-5);
+        assert!(raycast_camera.get_ray((0., 0.), (0., 0.), 0.).is_none());
+
+        camera.projection = Projection::Equirectangular;
+        let raycast_camera = RaycastCamera::from_camera(&camera);
+        let directions = [((0.5, 0.5), -Vec3::z()), ((0.75, 0.5), Vec3::x()), ((0., 0.5), Vec3::z()), ((0.3, 0.), Vec3::y())];
+        for &(uv, expected) in &directions {
+            let ray = raycast_camera.get_ray(uv, (0., 0.), 0.).unwrap();
+            assert!((ray.direction - expected).norm() < 1e-5, "{:?} {}", uv, ray.direction);
+        }
+    }
+
+    #[test]
+    fn test_shutter() {
+        let mut camera = Camera::new(Vec3::zeros(), -Vec3::z(), Vec3::y(), 60., 1.);
+        camera.shutter = (0.25, 0.75);
+        camera.motion = Vec3::new(4., 0., 0.);
+        let raycast_camera = RaycastCamera::from_camera(&camera);
+
+        assert_eq!(raycast_camera.shutter_time(0.), 0.25);
+        assert_eq!(raycast_camera.shutter_time(0.5), 0.5);
+
+        let ray = raycast_camera.get_ray((0.5, 0.5), (0., 0.), 0.5).unwrap();
+        assert_eq!(ray.time, 0.5);
+        assert!((ray.origin - Vec3::new(2., 0., 0.)).norm() < 1e-6);
+        assert!((ray.direction + Vec3::z()).norm() < 1e-6);
+    }
+}
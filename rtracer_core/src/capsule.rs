@@ -0,0 +1,70 @@
+use crate::Vec3;
+use crate::ray::Ray;
+use crate::aabb::Aabb;
+use crate::intersect::Intersect;
+use crate::intersection::{ray_capsule_crossings, nearest_crossing};
+use crate::bounded::Bounded;
+
+// points within radius of segment a, b
+#[derive(Copy, Clone, Debug)]
+pub struct Capsule {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub radius: f32,
+}
+
+impl Capsule {
+    pub fn new(a: Vec3, b: Vec3, radius: f32) -> Capsule {
+        Capsule { a, b, radius }
+    }
+
+    pub fn normal_at(&self, point: &Vec3) -> Vec3 {
+        let ab = self.b - self.a;
+        let h = (Vec3::dot(&(point - self.a), &ab) / ab.norm_squared()).clamp(0., 1.);
+        (point - (self.a + h * ab)).normalize()
+    }
+}
+
+impl Intersect for Capsule {
+    fn intersect(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<f32> {
+        nearest_crossing(&ray_capsule_crossings(ray, self), t_min_max)
+    }
+}
+
+impl Bounded for Capsule {
+    fn aabb(&self) -> Aabb {
+        let size = Vec3::repeat(2. * self.radius);
+        Aabb::union(&Aabb::from_center_size(self.a, size), &Aabb::from_center_size(self.b, size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capsule_hits() {
+        let capsule = Capsule::new(Vec3::zeros(), Vec3::new(0., 2., 0.), 0.5);
+
+        // side and cap
+        let ray = Ray::new(Vec3::new(0., 1., 5.), -Vec3::z());
+        assert!(relative_eq!(capsule.intersect(&ray, (0., std::f32::MAX)).unwrap(), 4.5, epsilon = 1e-5));
+        assert!(relative_eq!(capsule.intersect(&ray, (5., std::f32::MAX)).unwrap(), 5.5, epsilon = 1e-5));
+        let ray = Ray::new(Vec3::new(0., 5., 0.), -Vec3::y());
+        let t = capsule.intersect(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 2.5, epsilon = 1e-5));
+        assert!((capsule.normal_at(&ray.point_at_parameter(t)) - Vec3::y()).norm() < 1e-4);
+
+        // rounded end, from inside too
+        let ray = Ray::new(Vec3::new(0., -0.3, 5.), -Vec3::z());
+        assert!(relative_eq!(capsule.intersect(&ray, (0., std::f32::MAX)).unwrap(), 4.6, epsilon = 1e-5));
+        let ray = Ray::new(Vec3::new(0., 1., 0.), Vec3::y());
+        assert!(relative_eq!(capsule.intersect(&ray, (0., std::f32::MAX)).unwrap(), 1.5, epsilon = 1e-5));
+
+        assert!(capsule.intersect(&Ray::new(Vec3::new(0., 2.6, 5.), -Vec3::z()), (0., std::f32::MAX)).is_none());
+
+        let aabb = capsule.aabb();
+        assert!((aabb.min - Vec3::new(-0.5, -0.5, -0.5)).norm() < 1e-6);
+        assert!((aabb.max - Vec3::new(0.5, 2.5, 0.5)).norm() < 1e-6);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::Vec3;
+use crate::ray::Ray;
+use crate::aabb::Aabb;
+use crate::intersect::Intersect;
+use crate::intersection::{ray_cone_crossings, nearest_crossing};
+use crate::bounded::Bounded;
+use crate::disk::disk_extent;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Cone {
+    // center of the base disk
+    pub base: Vec3,
+    pub apex: Vec3,
+    // radius of the base
+    pub radius: f32,
+    pub capped: bool,
+}
+
+impl Cone {
+    pub fn new(base: Vec3, apex: Vec3, radius: f32) -> Cone {
+        Cone { base, apex, radius, capped: true }
+    }
+
+    // without base disk
+    pub fn open(base: Vec3, apex: Vec3, radius: f32) -> Cone {
+        Cone { base, apex, radius, capped: false }
+    }
+
+    // from apex to base
+    pub fn axis(&self) -> Vec3 {
+        (self.base - self.apex).normalize()
+    }
+
+    pub fn normal_at(&self, point: &Vec3) -> Vec3 {
+        let axis = self.axis();
+        let height = (self.base - self.apex).norm();
+        let q = point - self.apex;
+        let h = Vec3::dot(&q, &axis);
+
+        if self.capped {
+            let radial = (q - h * axis).norm();
+            let side = (radial - h * self.radius / height).abs() * height / (height * height + self.radius * self.radius).sqrt();
+            if (h - height).abs() < side {
+                return axis
+            }
+        }
+
+        // gradient of (q.axis)^2 - cos^2 |q|^2 pointing out
+        let cos2 = height * height / (height * height + self.radius * self.radius);
+        (cos2 * q - h * axis).normalize()
+    }
+}
+
+impl Intersect for Cone {
+    fn intersect(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<f32> {
+        nearest_crossing(&ray_cone_crossings(ray, self), t_min_max)
+    }
+}
+
+impl Bounded for Cone {
+    fn aabb(&self) -> Aabb {
+        let base = Aabb::from_center_size(self.base, 2. * disk_extent(&self.axis(), self.radius));
+        Aabb::union(&base, &Aabb::new(self.apex, self.apex))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cone_hits() {
+        // 45 degrees cone standing on xz plane
+        let cone = Cone::new(Vec3::zeros(), Vec3::new(0., 1., 0.), 1.);
+
+        let ray = Ray::new(Vec3::new(0., 0.5, 5.), -Vec3::z());
+        let t = cone.intersect(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 4.5, epsilon = 1e-5));
+        let normal = cone.normal_at(&ray.point_at_parameter(t));
+        assert!((normal - Vec3::new(0., 1., 1.).normalize()).norm() < 1e-4);
+        assert!(relative_eq!(cone.intersect(&ray, (5., std::f32::MAX)).unwrap(), 5.5, epsilon = 1e-5));
+
+        // base from below, apex side of the double cone is not there
+        let ray = Ray::new(Vec3::new(0.3, -2., 0.), Vec3::y());
+        let t = cone.intersect(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 2., epsilon = 1e-5));
+        assert!((cone.normal_at(&ray.point_at_parameter(t)) + Vec3::y()).norm() < 1e-4);
+        assert!(cone.intersect(&Ray::new(Vec3::new(0., 1.5, 5.), -Vec3::z()), (0., std::f32::MAX)).is_none());
+
+        // open cone is hit on the inner side
+        let open = Cone::open(Vec3::zeros(), Vec3::new(0., 1., 0.), 1.);
+        let t = open.intersect(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 2.7, epsilon = 1e-4));
+
+        let aabb = cone.aabb();
+        assert!((aabb.min - Vec3::new(-1., 0., -1.)).norm() < 1e-6);
+        assert!((aabb.max - Vec3::new(1., 1., 1.)).norm() < 1e-6);
+    }
+}
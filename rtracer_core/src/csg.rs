@@ -0,0 +1,293 @@
+use std::sync::Arc;
+use std::cmp::Ordering;
+
+use serde::{Serialize, Deserialize};
+
+use crate::Vec3;
+use crate::ray::Ray;
+use crate::aabb::Aabb;
+use crate::intersect::{Intersect, SurfaceHit};
+use crate::intersection::*;
+use crate::bounded::Bounded;
+use crate::primitive::Primitive;
+use crate::sphere::Sphere;
+use crate::cube::Cube;
+use crate::cylinder::Cylinder;
+use crate::cone::Cone;
+use crate::capsule::Capsule;
+use crate::torus::Torus;
+use crate::instance::Instance;
+
+// point where the ray enters or leaves a solid, normal points out of the solid
+#[derive(Copy, Clone, Debug)]
+pub struct Crossing {
+    pub t: f32,
+    pub normal: Vec3,
+}
+
+// part of the ray inside of a solid
+#[derive(Copy, Clone, Debug)]
+pub struct Span {
+    pub enter: Crossing,
+    pub exit: Crossing,
+}
+
+// closed primitive with inside and outside
+pub trait Solid {
+    // disjoint spans sorted by t, also behind the ray origin
+    fn spans(&self, ray: &Ray) -> Vec<Span>;
+}
+
+// pairs sorted crossings of a closed surface, tangent hits without a pair are dropped,
+// so are nan of degenerate primitives or rays
+fn spans_from_crossings<I, N>(ray: &Ray, ts: I, normal_at: N) -> Vec<Span>
+    where I: IntoIterator<Item = f32>, N: Fn(&Vec3) -> Vec3 {
+    let mut ts: Vec<_> = ts.into_iter().filter(|t| t.is_finite()).collect();
+    ts.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    let crossing = |t: f32| Crossing { t, normal: normal_at(&ray.point_at_parameter(t)) };
+    ts.chunks_exact(2)
+        .map(|pair| Span { enter: crossing(pair[0]), exit: crossing(pair[1]) })
+        .collect()
+}
+
+impl Solid for Sphere {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let sphere = self.at_time(ray.time);
+        match ray_sphere_crossings(ray, &sphere) {
+            Some((t1, t2)) => spans_from_crossings(ray, vec![t1, t2], |p| sphere.normal_at(p)),
+            None => vec![],
+        }
+    }
+}
+
+impl Solid for Cube {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        match ray_aabb_crossings(ray, &self.aabb()) {
+            Some((t1, t2)) => spans_from_crossings(ray, vec![t1, t2], |p| self.normal_at(p)),
+            None => vec![],
+        }
+    }
+}
+
+// open cylinders and cones are not closed, their spans are meaningless
+impl Solid for Cylinder {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        spans_from_crossings(ray, ray_cylinder_crossings(ray, self).iter().filter_map(|t| *t), |p| self.normal_at(p))
+    }
+}
+
+impl Solid for Cone {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        spans_from_crossings(ray, ray_cone_crossings(ray, self).iter().filter_map(|t| *t), |p| self.normal_at(p))
+    }
+}
+
+impl Solid for Capsule {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        spans_from_crossings(ray, ray_capsule_crossings(ray, self).iter().filter_map(|t| *t), |p| self.normal_at(p))
+    }
+}
+
+impl Solid for Torus {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        spans_from_crossings(ray, ray_torus_crossings(ray, self).iter().filter_map(|t| *t), |p| self.normal_at(p))
+    }
+}
+
+impl Solid for Instance {
+    // t is kept by the transform of the ray, only normals go back to world space
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let to_world = |c: Crossing| Crossing { normal: self.transform.transform_normal(&c.normal), ..c };
+        self.primitive.spans(&self.to_local(ray)).into_iter()
+            .map(|s| Span { enter: to_world(s.enter), exit: to_world(s.exit) })
+            .collect()
+    }
+}
+
+impl Solid for Primitive {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        match self {
+            Primitive::Sphere(s) => s.spans(ray),
+            Primitive::Cube(s) => s.spans(ray),
+            Primitive::Cylinder(s) => s.spans(ray),
+            Primitive::Cone(s) => s.spans(ray),
+            Primitive::Capsule(s) => s.spans(ray),
+            Primitive::Torus(s) => s.spans(ray),
+            Primitive::Instance(s) => s.spans(ray),
+            Primitive::Csg(s) => s.spans(ray),
+            // scene files reject other children, !todo: planes are half spaces, closed meshes and sdf have insides too
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // a without b
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// boolean operation on solids, children can be csg too
+#[derive(Clone, Debug)]
+pub struct Csg {
+    pub op: CsgOp,
+    pub a: Arc<Primitive>,
+    pub b: Arc<Primitive>,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Arc<Primitive>, b: Arc<Primitive>) -> Csg {
+        Csg { op, a, b }
+    }
+
+    pub fn hit_surface(&self, ray: &Ray, (t_min, t_max): (f32, f32)) -> Option<SurfaceHit> {
+        self.spans(ray).iter()
+            .flat_map(|s| std::iter::once(s.enter).chain(std::iter::once(s.exit)))
+            .find(|c| c.t > t_min)
+            .filter(|c| c.t < t_max)
+            .map(|c| SurfaceHit::new(c.t, c.normal.normalize()))
+    }
+}
+
+impl Solid for Csg {
+    // sweep over crossings of both children, result changes state where the operation does
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let mut events: Vec<(Crossing, bool, bool)> = vec![];
+        for (spans, from_a) in [(self.a.spans(ray), true), (self.b.spans(ray), false)].iter() {
+            for s in spans {
+                events.push((s.enter, true, *from_a));
+                events.push((s.exit, false, *from_a));
+            }
+        }
+        events.sort_by(|a, b| a.0.t.partial_cmp(&b.0.t).unwrap_or(Ordering::Equal));
+
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter = None;
+        let mut spans = vec![];
+        for (crossing, entering, from_a) in events {
+            let was_inside = self.op.inside(in_a, in_b);
+            if from_a { in_a = entering } else { in_b = entering }
+            let inside = self.op.inside(in_a, in_b);
+
+            // surface of subtracted solid faces into it
+            let crossing = if !from_a && self.op == CsgOp::Difference {
+                Crossing { normal: -crossing.normal, ..crossing }
+            } else {
+                crossing
+            };
+
+            if !was_inside && inside {
+                enter = Some(crossing);
+            } else if was_inside && !inside {
+                if let Some(enter) = enter.take() {
+                    spans.push(Span { enter, exit: crossing });
+                }
+            }
+        }
+        spans
+    }
+}
+
+impl Intersect for Csg {
+    fn intersect(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<f32> {
+        self.hit_surface(ray, t_min_max).map(|hit| hit.t)
+    }
+}
+
+impl Bounded for Csg {
+    fn aabb(&self) -> Aabb {
+        let (a, b) = (self.a.aabb(), self.b.aabb());
+        match self.op {
+            CsgOp::Union => Aabb::union(&a, &b),
+            CsgOp::Intersection => Aabb::new(a.min.zip_map(&b.min, f32::max), a.max.zip_map(&b.max, f32::min)),
+            CsgOp::Difference => a,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ball() -> Arc<Primitive> {
+        Arc::new(Sphere::new(Vec3::zeros(), 1.).into())
+    }
+
+    fn corner_box() -> Arc<Primitive> {
+        Arc::new(Cube::new(Vec3::new(1., 1., 1.), Vec3::repeat(2.)).into())
+    }
+
+    #[test]
+    fn test_sphere_with_box_cut_out() {
+        let csg = Csg::new(CsgOp::Difference, ball(), corner_box());
+
+        // outside of the cut the sphere is untouched
+        let hit = csg.hit_surface(&Ray::new(Vec3::new(-0.5, -0.5, 5.), -Vec3::z()), (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(hit.t, 5. - 0.5f32.sqrt(), epsilon = 1e-4));
+
+        // ray into the cut hits the box wall at z = 0, facing the ray
+        let ray = Ray::new(Vec3::new(0.5, 0.5, 5.), -Vec3::z());
+        let hit = csg.hit_surface(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(hit.t, 5., epsilon = 1e-4));
+        assert!((hit.normal - Vec3::z()).norm() < 1e-4);
+
+        // and leaves through the sphere at the back
+        let spans = csg.spans(&ray);
+        assert_eq!(spans.len(), 1);
+        assert!(relative_eq!(spans[0].exit.t, 5. + 0.5f32.sqrt(), epsilon = 1e-4));
+        assert!(spans[0].exit.normal.z < 0.);
+
+        // inside of the remaining part, next surface is the cut
+        let hit = csg.hit_surface(&Ray::new(Vec3::new(0.5, 0.5, -0.5), Vec3::z()), (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(hit.t, 0.5, epsilon = 1e-4));
+        assert!((hit.normal - Vec3::z()).norm() < 1e-4);
+    }
+
+    #[test]
+    fn test_union_intersection() {
+        let ray = Ray::new(Vec3::new(0.5, 0.5, 5.), -Vec3::z());
+
+        let union = Csg::new(CsgOp::Union, ball(), corner_box());
+        let spans = union.spans(&ray);
+        assert_eq!(spans.len(), 1);
+        assert!(relative_eq!(spans[0].enter.t, 3., epsilon = 1e-4));
+        assert!(relative_eq!(spans[0].exit.t, 5. + 0.5f32.sqrt(), epsilon = 1e-4));
+
+        let intersection = Csg::new(CsgOp::Intersection, ball(), corner_box());
+        let spans = intersection.spans(&ray);
+        assert!(relative_eq!(spans[0].enter.t, 5. - 0.5f32.sqrt(), epsilon = 1e-4));
+        assert!(relative_eq!(spans[0].exit.t, 5., epsilon = 1e-4));
+        let aabb = intersection.aabb();
+        assert!((aabb.min - Vec3::zeros()).norm() < 1e-6 && (aabb.max - Vec3::repeat(1.)).norm() < 1e-6);
+
+        // nested with transformed child
+        let moved = Arc::new(Instance::new(ball(), crate::transform::Transform::translation(Vec3::new(0.5, 0.5, -2.))).into());
+        let nested = Csg::new(CsgOp::Union, Arc::new(union.into()), moved);
+        assert_eq!(nested.spans(&ray).len(), 2);
+    }
+
+    #[test]
+    fn test_degenerate_children() {
+        let flat = Arc::new(Cylinder::new(Vec3::zeros(), Vec3::zeros(), 1.).into());
+        let csg = Csg::new(CsgOp::Union, ball(), flat);
+        let ray = Ray::new(Vec3::new(0., 0., 5.), -Vec3::z());
+        assert!(relative_eq!(csg.hit_surface(&ray, (0., std::f32::MAX)).unwrap().t, 4., epsilon = 1e-4));
+
+        let still = Ray::new(Vec3::new(0., 0., 5.), Vec3::zeros());
+        assert!(csg.hit_surface(&still, (0., std::f32::MAX)).is_none());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::Vec3;
+use crate::ray::Ray;
+use crate::aabb::Aabb;
+use crate::intersect::Intersect;
+use crate::intersection::{ray_cylinder_crossings, nearest_crossing};
+use crate::bounded::Bounded;
+use crate::disk::disk_extent;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Cylinder {
+    // centers of bottom and top caps
+    pub base: Vec3,
+    pub top: Vec3,
+    pub radius: f32,
+    pub capped: bool,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3, top: Vec3, radius: f32) -> Cylinder {
+        Cylinder { base, top, radius, capped: true }
+    }
+
+    // tube without caps
+    pub fn open(base: Vec3, top: Vec3, radius: f32) -> Cylinder {
+        Cylinder { base, top, radius, capped: false }
+    }
+
+    pub fn axis(&self) -> Vec3 {
+        (self.top - self.base).normalize()
+    }
+
+    pub fn normal_at(&self, point: &Vec3) -> Vec3 {
+        let axis = self.axis();
+        let h = Vec3::dot(&(point - self.base), &axis);
+        let radial = point - (self.base + h * axis);
+
+        // surface closest to the point
+        if self.capped {
+            let height = (self.top - self.base).norm();
+            let side = (radial.norm() - self.radius).abs();
+            if h.abs() < side && h.abs() < (h - height).abs() {
+                return -axis
+            }
+            if (h - height).abs() < side {
+                return axis
+            }
+        }
+        radial.normalize()
+    }
+}
+
+impl Intersect for Cylinder {
+    fn intersect(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<f32> {
+        nearest_crossing(&ray_cylinder_crossings(ray, self), t_min_max)
+    }
+}
+
+impl Bounded for Cylinder {
+    fn aabb(&self) -> Aabb {
+        let size = 2. * disk_extent(&self.axis(), self.radius);
+        Aabb::union(&Aabb::from_center_size(self.base, size), &Aabb::from_center_size(self.top, size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cylinder_hits() {
+        let cylinder = Cylinder::new(Vec3::zeros(), Vec3::new(0., 2., 0.), 0.5);
+
+        // side
+        let ray = Ray::new(Vec3::new(0., 1., 5.), -Vec3::z());
+        let t = cylinder.intersect(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 4.5, epsilon = 1e-5));
+        assert!((cylinder.normal_at(&ray.point_at_parameter(t)) - Vec3::z()).norm() < 1e-4);
+        // near crossing before t_min does not hide the far one
+        assert!(relative_eq!(cylinder.intersect(&ray, (5., std::f32::MAX)).unwrap(), 5.5, epsilon = 1e-5));
+
+        // top cap, and from inside to the bottom cap
+        let ray = Ray::new(Vec3::new(0.2, 5., 0.), -Vec3::y());
+        let t = cylinder.intersect(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 3., epsilon = 1e-5));
+        assert!((cylinder.normal_at(&ray.point_at_parameter(t)) - Vec3::y()).norm() < 1e-4);
+        let ray = Ray::new(Vec3::new(0.2, 1., 0.), -Vec3::y());
+        assert!(relative_eq!(cylinder.intersect(&ray, (0., std::f32::MAX)).unwrap(), 1., epsilon = 1e-5));
+
+        // open tube is seen through along the axis, misses above it
+        let open = Cylinder::open(Vec3::zeros(), Vec3::new(0., 2., 0.), 0.5);
+        assert!(open.intersect(&Ray::new(Vec3::new(0.2, 5., 0.), -Vec3::y()), (0., std::f32::MAX)).is_none());
+        assert!(open.intersect(&Ray::new(Vec3::new(0., 2.5, 5.), -Vec3::z()), (0., std::f32::MAX)).is_none());
+
+        let aabb = Cylinder::new(Vec3::zeros(), Vec3::new(2., 0., 0.), 1.).aabb();
+        assert!((aabb.min - Vec3::new(0., -1., -1.)).norm() < 1e-6);
+        assert!((aabb.max - Vec3::new(2., 1., 1.)).norm() < 1e-6);
+    }
+}
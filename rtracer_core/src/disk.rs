@@ -1,3 +1,4 @@
+use crate::Vec3;
 use crate::plane::Plane;
 use crate::ray:: Ray;
 use crate::aabb::Aabb;
@@ -31,6 +32,31 @@ impl Intersect for Disk {
 
 impl Bounded for Disk {
     fn aabb(&self) -> Aabb {
-        unimplemented!()
+        Aabb::from_center_size(self.plane.origin, 2. * disk_extent(&self.plane.normal, self.radius))
+    }
+}
+
+// half size of bounds of disk with given normal and radius
+pub(crate) fn disk_extent(normal: &Vec3, radius: f32) -> Vec3 {
+    normal.map(|n| radius * (1. - n * n).max(0.).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_aabb() {
+        let disk = Disk::new(Plane::new(Vec3::new(1., 2., 3.), Vec3::z()), 2.);
+        let aabb = disk.aabb();
+        assert!((aabb.min - Vec3::new(-1., 0., 3.)).norm() < 1e-6);
+        assert!((aabb.max - Vec3::new(3., 4., 3.)).norm() < 1e-6);
+
+        // tilted by 45 degrees around y
+        let normal = Vec3::new(1., 0., 1.).normalize();
+        let aabb = Disk::new(Plane::new(Vec3::zeros(), normal), 1.).aabb();
+        assert!(relative_eq!(aabb.max.x, 0.5f32.sqrt(), epsilon = 1e-5));
+        assert!(relative_eq!(aabb.max.y, 1., epsilon = 1e-5));
+        assert!(relative_eq!(aabb.max.z, 0.5f32.sqrt(), epsilon = 1e-5));
     }
 }
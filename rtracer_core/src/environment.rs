@@ -0,0 +1,302 @@
+use std::f32::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{Vec3, Vec2};
+use crate::image::{Image, ImageError};
+use crate::sky::Sky;
+
+// radiance coming from directions where rays leave the scene
+#[derive(Clone)]
+pub enum Background {
+    Color(Vec3),
+    Environment(Arc<EnvironmentMap>),
+    Sky(Arc<Sky>),
+}
+
+impl Background {
+    pub fn radiance(&self, direction: &Vec3) -> Vec3 {
+        match self {
+            Background::Color(color) => *color,
+            Background::Environment(map) => map.radiance(direction),
+            Background::Sky(sky) => sky.radiance(direction),
+        }
+    }
+
+    // constant color is not importance sampled
+    pub fn is_light(&self) -> bool {
+        match self {
+            Background::Color(_) => false,
+            Background::Environment(_) | Background::Sky(_) => true,
+        }
+    }
+
+    // direction with its solid angle pdf
+    pub fn sample(&self, u: (f32, f32)) -> Option<(Vec3, f32)> {
+        match self {
+            Background::Color(_) => None,
+            Background::Environment(map) => map.sample(u),
+            Background::Sky(sky) => sky.sample(u),
+        }
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f32 {
+        match self {
+            Background::Color(_) => 0.,
+            Background::Environment(map) => map.pdf(direction),
+            Background::Sky(sky) => sky.pdf(direction),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Background {
+        Background::Color(Vec3::repeat(1.))
+    }
+}
+
+impl From<Vec3> for Background {
+    fn from(color: Vec3) -> Self {
+        Background::Color(color)
+    }
+}
+
+impl From<EnvironmentMap> for Background {
+    fn from(map: EnvironmentMap) -> Self {
+        Background::Environment(Arc::new(map))
+    }
+}
+
+impl From<Sky> for Background {
+    fn from(sky: Sky) -> Self {
+        Background::Sky(Arc::new(sky))
+    }
+}
+
+fn luminance(c: &Vec3) -> f32 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+// piecewise constant distribution over image pixels, rows are picked by marginal cdf, then column in the row
+struct Distribution2D {
+    width: usize,
+    height: usize,
+    func: Vec<f32>,
+    // width + 1 values per row
+    conditional_cdf: Vec<f32>,
+    marginal_cdf: Vec<f32>,
+    // mean of func
+    integral: f32,
+}
+
+impl Distribution2D {
+    fn new(width: usize, height: usize, func: Vec<f32>) -> Distribution2D {
+        let mut conditional_cdf = Vec::with_capacity(height * (width + 1));
+        let mut row_sums = Vec::with_capacity(height);
+        for row in func.chunks_exact(width) {
+            let sum = push_cdf(&mut conditional_cdf, row);
+            row_sums.push(sum);
+        }
+
+        let mut marginal_cdf = Vec::with_capacity(height + 1);
+        let total = push_cdf(&mut marginal_cdf, &row_sums);
+
+        let integral = total / (width * height) as f32;
+        Distribution2D { width, height, func, conditional_cdf, marginal_cdf, integral }
+    }
+
+    // continuous uv in [0, 1] and pdf with respect to uv area
+    fn sample(&self, (u1, u2): (f32, f32)) -> (Vec2, f32) {
+        let (row, dv) = sample_cdf(&self.marginal_cdf, u1);
+        let row_cdf = &self.conditional_cdf[row * (self.width + 1)..(row + 1) * (self.width + 1)];
+        let (column, du) = sample_cdf(row_cdf, u2);
+
+        let uv = Vec2::new((column as f32 + du) / self.width as f32, (row as f32 + dv) / self.height as f32);
+        (uv, self.func[row * self.width + column] / self.integral)
+    }
+
+    fn pdf(&self, uv: &Vec2) -> f32 {
+        let column = ((uv.x * self.width as f32) as usize).min(self.width - 1);
+        let row = ((uv.y * self.height as f32) as usize).min(self.height - 1);
+        self.func[row * self.width + column] / self.integral
+    }
+}
+
+// appends normalized cdf of values, returns their sum
+fn push_cdf(cdf: &mut Vec<f32>, values: &[f32]) -> f32 {
+    let sum: f32 = values.iter().sum();
+    let n = values.len() as f32;
+
+    let mut acc = 0.;
+    cdf.push(0.);
+    for (i, v) in values.iter().enumerate() {
+        acc += v;
+        // zero rows are sampled uniformly
+        cdf.push(if sum > 0. { acc / sum } else { (i + 1) as f32 / n });
+    }
+    sum
+}
+
+// index of the segment containing u and offset inside it
+fn sample_cdf(cdf: &[f32], u: f32) -> (usize, f32) {
+    let idx = (cdf.partition_point(|c| *c <= u).max(1) - 1).min(cdf.len() - 2);
+    let width = cdf[idx + 1] - cdf[idx];
+    let offset = if width > 0. { ((u - cdf[idx]) / width).min(1.) } else { 0.5 };
+    (idx, offset)
+}
+
+// rotation is in degrees around y
+pub(crate) fn equirect_direction(uv: &Vec2, rotation: f32) -> Vec3 {
+    let phi = 2. * PI * (uv.x - 0.5) + rotation.to_radians();
+    let theta = PI * uv.y;
+    Vec3::new(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos())
+}
+
+// equirectangular hdr image, center of the image looks to -z, top row is +y
+pub struct EnvironmentMap {
+    image: Image,
+    // degrees around y
+    rotation: f32,
+    intensity: f32,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Image, rotation: f32, intensity: f32) -> EnvironmentMap {
+        debug_assert!(image.width() > 0 && image.height() > 0);
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        // bright pixels are sampled more, rows near poles cover less solid angle
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+            for x in 0..width {
+                func.push(luminance(&image[(x as u32, y as u32)]).max(0.) * sin_theta);
+            }
+        }
+
+        // black map, fall back to uniform sampling of the sphere
+        if func.iter().all(|f| *f == 0.) {
+            for (i, f) in func.iter_mut().enumerate() {
+                *f = (PI * ((i / width) as f32 + 0.5) / height as f32).sin();
+            }
+        }
+
+        let distribution = Distribution2D::new(width, height, func);
+        EnvironmentMap { image, rotation, intensity, distribution }
+    }
+
+    pub fn load(path: &Path, rotation: f32, intensity: f32) -> Result<EnvironmentMap, ImageError> {
+        Ok(EnvironmentMap::new(Image::load(path)?, rotation, intensity))
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn direction_to_uv(&self, direction: &Vec3) -> Vec2 {
+        let d = direction.normalize();
+        let phi = d.x.atan2(-d.z) - self.rotation.to_radians();
+        let theta = d.y.clamp(-1., 1.).acos();
+        Vec2::new((0.5 + phi / (2. * PI)).rem_euclid(1.), theta / PI)
+    }
+
+    pub fn uv_to_direction(&self, uv: &Vec2) -> Vec3 {
+        equirect_direction(uv, self.rotation)
+    }
+
+    // nearest pixel, so radiance is constant where pdf is
+    pub fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let uv = self.direction_to_uv(direction);
+        let (width, height) = (self.image.width(), self.image.height());
+        let x = ((uv.x * width as f32) as u32).min(width - 1);
+        let y = ((uv.y * height as f32) as u32).min(height - 1);
+        self.image[(x, y)] * self.intensity
+    }
+
+    // direction with its solid angle pdf, None at poles where pdf is undefined
+    pub fn sample(&self, u: (f32, f32)) -> Option<(Vec3, f32)> {
+        let (uv, pdf) = self.distribution.sample(u);
+        let sin_theta = (PI * uv.y).sin();
+        if pdf <= 0. || sin_theta <= 0. {
+            return None
+        }
+        Some((self.uv_to_direction(&uv), pdf / (2. * PI * PI * sin_theta)))
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f32 {
+        let uv = self.direction_to_uv(direction);
+        let sin_theta = (PI * uv.y).sin();
+        if sin_theta <= 0. {
+            return 0.
+        }
+        self.distribution.pdf(&uv) / (2. * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_map() -> EnvironmentMap {
+        // dark map with a bright spot
+        let mut image = Image::with_background(16, 8, Vec3::repeat(0.1));
+        image[(11, 2)] = Vec3::new(50., 40., 30.);
+        EnvironmentMap::new(image, 30., 2.)
+    }
+
+    #[test]
+    fn test_direction_uv_roundtrip() {
+        let map = test_map();
+        for &(u, v) in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7), (0.3, 0.95)] {
+            let uv = Vec2::new(u, v);
+            let back = map.direction_to_uv(&map.uv_to_direction(&uv));
+            assert!((back - uv).norm() < 1e-4, "{} {}", uv, back);
+        }
+
+        // unrotated center of the image looks forward
+        let map = EnvironmentMap::new(Image::new(4, 2), 0., 1.);
+        assert!((map.uv_to_direction(&Vec2::new(0.5, 0.5)) - Vec3::new(0., 0., -1.)).norm() < 1e-6);
+        assert_eq!(map.direction_to_uv(&Vec3::y()).y, 0.);
+    }
+
+    #[test]
+    fn test_sample_pdf() {
+        let map = test_map();
+        let mut bright = 0;
+        let n = 1000;
+        for i in 0..n {
+            let u = ((i as f32 + 0.5) / n as f32, (i * 7 % n) as f32 / n as f32);
+            let (direction, pdf) = map.sample(u).unwrap();
+            assert!(relative_eq!(pdf, map.pdf(&direction), max_relative = 1e-3), "{} != {}", pdf, map.pdf(&direction));
+            if map.radiance(&direction).x > 1. {
+                bright += 1;
+            }
+        }
+        // spot is 1 of 128 pixels but gets most of samples
+        assert!(bright > n / 2, "{}", bright);
+    }
+
+    #[test]
+    fn test_pdf_integrates_to_one() {
+        let map = test_map();
+        let (nu, nv) = (256, 128);
+        let mut sum = 0.;
+        for j in 0..nv {
+            for i in 0..nu {
+                let uv = Vec2::new((i as f32 + 0.5) / nu as f32, (j as f32 + 0.5) / nv as f32);
+                let sin_theta = (PI * uv.y).sin();
+                sum += map.pdf(&map.uv_to_direction(&uv)) * sin_theta * 2. * PI * PI / (nu * nv) as f32;
+            }
+        }
+        assert!(relative_eq!(sum, 1., epsilon = 1e-3), "{}", sum);
+    }
+}
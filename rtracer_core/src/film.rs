@@ -0,0 +1,145 @@
+use serde::{Serialize, Deserialize};
+
+use crate::image::{Image, ColorRGB};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapping {
+    Clamp,
+    Reinhard,
+    // Narkowicz fit of aces filmic curve
+    Aces,
+}
+
+impl ToneMapping {
+    pub fn from_name(name: &str) -> Option<ToneMapping> {
+        match name.to_lowercase().as_str() {
+            "clamp" => Some(ToneMapping::Clamp),
+            "reinhard" => Some(ToneMapping::Reinhard),
+            "aces" => Some(ToneMapping::Aces),
+            _ => None,
+        }
+    }
+
+    pub fn map(&self, c: f32) -> f32 {
+        let c = c.max(0.);
+        match self {
+            ToneMapping::Clamp => c.min(1.),
+            ToneMapping::Reinhard => c / (1. + c),
+            ToneMapping::Aces => {
+                let (a, b, c2, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                ((c * (a * c + b)) / (c * (c2 * c + d) + e)).min(1.)
+            },
+        }
+    }
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping::Clamp
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// turns linear radiance of the renderer into display referred srgb colors
+#[derive(Copy, Clone, Debug)]
+pub struct Film {
+    // in stops, every stop doubles the brightness
+    exposure: f32,
+    tone_mapping: ToneMapping,
+}
+
+impl Film {
+    pub fn new() -> Film {
+        Film { exposure: 0., tone_mapping: ToneMapping::default() }
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure;
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
+    pub fn develop_color(&self, color: &ColorRGB) -> ColorRGB {
+        let scale = 2f32.powf(self.exposure);
+        color.map(|c| {
+            // nan samples become black instead of spreading through the image
+            let c = if c.is_nan() { 0. } else { c * scale };
+            linear_to_srgb(self.tone_mapping.map(c))
+        })
+    }
+
+    // source image is left untouched, so it can be developed again with other settings
+    pub fn develop(&self, image: &Image) -> Image {
+        let mut developed = Image::new(image.width(), image.height());
+        for (dst, src) in developed.buf_mut().iter_mut().zip(image.buf()) {
+            *dst = self.develop_color(src);
+        }
+        developed
+    }
+}
+
+impl Default for Film {
+    fn default() -> Film {
+        Film::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_roundtrip() {
+        for &c in &[0., 0.001, 0.2, 0.5, 1.] {
+            assert!(relative_eq!(srgb_to_linear(linear_to_srgb(c)), c, epsilon = 1e-5));
+        }
+        assert!(relative_eq!(linear_to_srgb(0.5), 0.735_357, epsilon = 1e-5));
+    }
+
+    #[test]
+    fn test_tone_mapping_range() {
+        for tone_mapping in &[ToneMapping::Clamp, ToneMapping::Reinhard, ToneMapping::Aces] {
+            assert_eq!(tone_mapping.map(-1.), 0.);
+            let mapped = tone_mapping.map(1000.);
+            assert!(mapped > 0.9 && mapped <= 1., "{:?} {}", tone_mapping, mapped);
+        }
+        assert_eq!(ToneMapping::Reinhard.map(1.), 0.5);
+    }
+
+    #[test]
+    fn test_exposure() {
+        let mut film = Film::new();
+        film.set_exposure(1.);
+
+        let developed = film.develop_color(&ColorRGB::new(0.25, 0.5, std::f32::NAN));
+        assert!(relative_eq!(developed.x, linear_to_srgb(0.5)));
+        assert!(relative_eq!(developed.y, 1.));
+        assert_eq!(developed.z, 0.);
+    }
+}
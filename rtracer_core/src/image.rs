@@ -1,19 +1,23 @@
 use std::ops::{Index, IndexMut};
-use std::io::Write;
+use std::io::{BufReader, BufWriter, Write};
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+use image::ColorType;
+use image::png::PNGEncoder;
+use image::hdr::{HDREncoder, HDRDecoder};
 
 use crate::Vec3;
+use crate::film::srgb_to_linear;
 
 pub type ColorRGB = Vec3;
 
 
+// hdr values are clamped, nan becomes black
 fn to_u8(color: &Vec3) -> (u8, u8, u8) {
-    debug_assert!(color.x >= 0f32 && color.x <= 1f32);
-    debug_assert!(color.y >= 0f32 && color.y <= 1f32);
-    debug_assert!(color.z >= 0f32 && color.z <= 1f32);
-
-    ((color.x * 255.99f32) as u8,
-     (color.y * 255.99f32) as u8,
-     (color.z * 255.99f32) as u8)
+    let to_u8 = |c: f32| (c.clamp(0., 1.) * 255.99f32) as u8;
+    (to_u8(color.x), to_u8(color.y), to_u8(color.z))
 }
 
 pub fn gamma_correction(color: &Vec3, gamma: f32) -> Vec3 {
@@ -22,6 +26,66 @@ pub fn gamma_correction(color: &Vec3, gamma: f32) -> Vec3 {
               color.z.powf(1. / gamma))
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    Hdr,
+    Pfm,
+    Exr,
+}
+
+impl ImageFormat {
+    pub fn from_extension(ext: &str) -> Option<ImageFormat> {
+        match ext.to_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "hdr" => Some(ImageFormat::Hdr),
+            "pfm" => Some(ImageFormat::Pfm),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        path.extension().and_then(|ext| ext.to_str()).and_then(ImageFormat::from_extension)
+    }
+
+    // float formats keep values out of [0, 1]
+    pub fn is_hdr(&self) -> bool {
+        match self {
+            ImageFormat::Ppm | ImageFormat::Png => false,
+            ImageFormat::Hdr | ImageFormat::Pfm | ImageFormat::Exr => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(std::io::Error),
+    UnknownFormat(String),
+    Exr(String),
+    Decode(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(err) => write!(f, "image io error: {}", err),
+            ImageError::UnknownFormat(path) => write!(f, "unknown image format of '{}'", path),
+            ImageError::Exr(err) => write!(f, "cant write exr image: {}", err),
+            ImageError::Decode(err) => write!(f, "cant decode image: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<std::io::Error> for ImageError {
+    fn from(err: std::io::Error) -> Self {
+        ImageError::Io(err)
+    }
+}
 
 pub struct Image {
     width: u32,
@@ -47,6 +111,10 @@ impl Image {
         self.height
     }
 
+    pub fn buf(&self) -> &[ColorRGB] {
+        &self.img
+    }
+
     pub fn buf_mut(&mut self) -> &mut Vec<ColorRGB> {
         &mut self.img
     }
@@ -63,6 +131,86 @@ impl Image {
         Ok(())
     }
 
+    pub fn write_png<T>(&self, file: &mut T) -> Result<(), std::io::Error>
+        where T: Write {
+        let mut data = Vec::with_capacity(self.img.len() * 3);
+        for pixel in &self.img {
+            let (r, g, b) = to_u8(pixel);
+            data.extend(&[r, g, b]);
+        }
+        PNGEncoder::new(file).encode(&data, self.width, self.height, ColorType::RGB(8))
+    }
+
+    // radiance rgbe
+    pub fn write_hdr<T>(&self, file: &mut T) -> Result<(), std::io::Error>
+        where T: Write {
+        let data: Vec<_> = self.img.iter().map(|c| image::Rgb([c.x, c.y, c.z])).collect();
+        HDREncoder::new(file).encode(&data, self.width as usize, self.height as usize)
+    }
+
+    // little endian, rows are stored from bottom to top
+    pub fn write_pfm<T>(&self, file: &mut T) -> Result<(), std::io::Error>
+        where T: Write {
+        write!(file, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.img.chunks(self.width as usize).rev() {
+            for pixel in row {
+                for c in pixel.iter() {
+                    file.write_all(&c.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // hdr files keep linear radiance, 8 bit formats are treated as srgb and linearized
+    pub fn load(path: &Path) -> Result<Image, ImageError> {
+        if ImageFormat::from_path(path) == Some(ImageFormat::Hdr) {
+            let decoder = HDRDecoder::new(BufReader::new(File::open(path)?))
+                .map_err(|err| ImageError::Decode(err.to_string()))?;
+            let metadata = decoder.metadata();
+            let data = decoder.read_image_hdr().map_err(|err| ImageError::Decode(err.to_string()))?;
+
+            let img = data.iter().map(|c| ColorRGB::new(c[0], c[1], c[2])).collect();
+            return Ok(Image { width: metadata.width, height: metadata.height, img })
+        }
+
+        let rgb = image::open(path).map_err(|err| ImageError::Decode(err.to_string()))?.to_rgb();
+        let img = rgb.pixels()
+            .map(|c| ColorRGB::new(c[0] as f32, c[1] as f32, c[2] as f32).map(|c| srgb_to_linear(c / 255.)))
+            .collect();
+        Ok(Image { width: rgb.width(), height: rgb.height(), img })
+    }
+
+    pub fn save_exr(&self, path: &Path) -> Result<(), ImageError> {
+        exr::prelude::write_rgb_file(path, self.width as usize, self.height as usize, |x, y| {
+            let c = self[(x as u32, y as u32)];
+            (c.x, c.y, c.z)
+        }).map_err(|err| ImageError::Exr(err.to_string()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ImageError> {
+        let format = ImageFormat::from_path(path)
+            .ok_or_else(|| ImageError::UnknownFormat(path.display().to_string()))?;
+        self.save_with_format(path, format)
+    }
+
+    pub fn save_with_format(&self, path: &Path, format: ImageFormat) -> Result<(), ImageError> {
+        if format == ImageFormat::Exr {
+            return self.save_exr(path)
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        match format {
+            ImageFormat::Ppm => self.write_ppm(&mut file)?,
+            ImageFormat::Png => self.write_png(&mut file)?,
+            ImageFormat::Hdr => self.write_hdr(&mut file)?,
+            ImageFormat::Pfm => self.write_pfm(&mut file)?,
+            ImageFormat::Exr => unreachable!(),
+        }
+        file.flush()?;
+        Ok(())
+    }
+
 //    pub fn iter(&self) -> impl Iterator<Item = &ColorRGB> {
 //        self.img.iter()
 //    }
@@ -85,3 +233,56 @@ impl IndexMut<(u32, u32)> for Image {
         &mut self.img[(y * self.width + x) as usize]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_u8_clamps() {
+        assert_eq!(to_u8(&Vec3::new(-1., 0.5, 20.)), (0, 127, 255));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ImageFormat::from_path(Path::new("out/image.EXR")), Some(ImageFormat::Exr));
+        assert_eq!(ImageFormat::from_path(Path::new("image.png")), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path(Path::new("image")), None);
+    }
+
+    #[test]
+    fn test_write_pfm() {
+        let mut img = Image::new(2, 2);
+        img[(0, 1)] = Vec3::new(1., 2., 3.);
+
+        let mut data = vec![];
+        img.write_pfm(&mut data).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(data.len(), header.len() + 2 * 2 * 3 * 4);
+        // bottom row goes first
+        assert_eq!(&data[header.len()..header.len() + 4], &1f32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_load_roundtrip() {
+        let mut img = Image::new(3, 2);
+        img[(2, 1)] = Vec3::new(4., 0.5, 0.25);
+        img[(0, 0)] = Vec3::new(1., 0., 0.);
+
+        let dir = std::env::temp_dir().join("rtracer_image_test");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        img.save(&dir.join("image.hdr")).unwrap();
+        let loaded = Image::load(&dir.join("image.hdr")).unwrap();
+        assert_eq!((loaded.width(), loaded.height()), (3, 2));
+        assert!((loaded[(2, 1)] - img[(2, 1)]).norm() < 0.05);
+
+        // png is stored in srgb and linearized back
+        img.save(&dir.join("image.png")).unwrap();
+        let loaded = Image::load(&dir.join("image.png")).unwrap();
+        assert!((loaded[(0, 0)] - img[(0, 0)]).norm() < 1e-6);
+        assert_eq!(loaded[(2, 1)].x, 1.);
+    }
+}
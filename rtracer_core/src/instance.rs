@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use crate::Vec3;
+use crate::ray::Ray;
+use crate::aabb::Aabb;
+use crate::intersect::{Intersect, SurfaceHit};
+use crate::bounded::Bounded;
+use crate::primitive::Primitive;
+use crate::transform::Transform;
+
+// shared geometry placed in the world by transform
+#[derive(Clone, Debug)]
+pub struct Instance {
+    pub primitive: Arc<Primitive>,
+    pub transform: Transform,
+    // world translation at time 1, applied after transform
+    pub motion: Vec3,
+}
+
+impl Instance {
+    pub fn new(primitive: Arc<Primitive>, transform: Transform) -> Instance {
+        Instance::moving(primitive, transform, Vec3::zeros())
+    }
+
+    pub fn moving(primitive: Arc<Primitive>, transform: Transform, motion: Vec3) -> Instance {
+        Instance { primitive, transform, motion }
+    }
+
+    pub(crate) fn to_local(&self, ray: &Ray) -> Ray {
+        let ray = Ray { origin: ray.origin - self.motion * ray.time, ..*ray };
+        self.transform.inverse_transform_ray(&ray)
+    }
+
+    pub fn hit_surface(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<SurfaceHit> {
+        self.primitive.hit_surface(&self.to_local(ray), t_min_max)
+            .map(|hit| SurfaceHit {
+                normal: self.transform.transform_normal(&hit.normal),
+                shading_normal: self.transform.transform_normal(&hit.shading_normal),
+                ..hit
+            })
+    }
+}
+
+impl Intersect for Instance {
+    fn intersect(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<f32> {
+        self.primitive.intersect(&self.to_local(ray), t_min_max)
+    }
+}
+
+impl Bounded for Instance {
+    // swept over whole motion
+    fn aabb(&self) -> Aabb {
+        let aabb = self.transform.transform_aabb(&self.primitive.aabb());
+        Aabb::union(&aabb, &Aabb::new(aabb.min + self.motion, aabb.max + self.motion))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::Sphere;
+    use crate::cube::Cube;
+    use crate::cylinder::Cylinder;
+    use crate::cone::Cone;
+    use crate::capsule::Capsule;
+    use crate::torus::Torus;
+
+    #[test]
+    fn test_scaled_sphere() {
+        let sphere = Arc::new(Primitive::Sphere(Sphere::new(Vec3::zeros(), 1.)));
+        let instance = Instance::new(sphere, Transform::scale(Vec3::new(2., 1., 1.))
+            .then(&Transform::translation(Vec3::new(0., 0., -5.))));
+
+        let ray = Ray::new(Vec3::new(-10., 0., -5.), Vec3::x());
+        let hit = instance.hit_surface(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(hit.t, 8., epsilon = 1e-5));
+        assert!((hit.normal + Vec3::x()).norm() < 1e-5);
+
+        let aabb = instance.aabb();
+        assert!((aabb.min - Vec3::new(-2., -1., -6.)).norm() < 1e-5);
+        assert!((aabb.max - Vec3::new(2., 1., -4.)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn test_scaled_analytic_primitives() {
+        // local rays of the instance have direction of half length, t must stay the same
+        let cases: Vec<(Primitive, Vec3, Vec3, f32)> = vec![
+            (Cylinder::new(Vec3::zeros(), Vec3::new(0., 2., 0.), 0.5).into(), Vec3::new(0., 2., 5.), -Vec3::z(), 4.),
+            (Cylinder::new(Vec3::zeros(), Vec3::new(0., 2., 0.), 0.5).into(), Vec3::new(0.4, 10., 0.), -Vec3::y(), 6.),
+            (Cone::new(Vec3::zeros(), Vec3::new(0., 1., 0.), 1.).into(), Vec3::new(0., 1., 5.), -Vec3::z(), 4.),
+            (Cone::new(Vec3::zeros(), Vec3::new(0., 1., 0.), 1.).into(), Vec3::new(0.6, -4., 0.), Vec3::y(), 4.),
+            (Capsule::new(Vec3::zeros(), Vec3::new(0., 2., 0.), 0.5).into(), Vec3::new(0., 2., 5.), -Vec3::z(), 4.),
+            (Capsule::new(Vec3::zeros(), Vec3::new(0., 2., 0.), 0.5).into(), Vec3::new(0., 10., 0.), -Vec3::y(), 5.),
+            (Torus::new(Vec3::new(0., 1., 0.), Vec3::y(), 1., 0.25).into(), Vec3::new(10., 2., 0.), -Vec3::x(), 7.5),
+            (Torus::new(Vec3::new(0., 1., 0.), Vec3::y(), 1., 0.25).into(), Vec3::new(0., 8., -2.), -Vec3::y(), 5.5),
+        ];
+
+        for (primitive, origin, direction, t) in cases {
+            let instance = Instance::new(Arc::new(primitive), Transform::scale(Vec3::repeat(2.)));
+            let hit = instance.intersect(&Ray::new(origin, direction), (0., std::f32::MAX))
+                .unwrap_or_else(|| panic!("{:?} is missed", instance.primitive));
+            assert!(relative_eq!(hit, t, epsilon = 1e-4), "{:?} is hit at {}", instance.primitive, hit);
+        }
+    }
+
+    #[test]
+    fn test_rotated_cube_normal() {
+        let cube = Arc::new(Primitive::Cube(Cube::new(Vec3::zeros(), Vec3::repeat(2.))));
+        let instance = Instance::new(cube, Transform::rotation(Vec3::y(), 45.));
+
+        let ray = Ray::new(Vec3::new(0.3, 0., 10.), -Vec3::z());
+        let hit = instance.hit_surface(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(hit.t, 10. - (2f32.sqrt() - 0.3), epsilon = 1e-4));
+        assert!((hit.normal - Vec3::new(1., 0., 1.).normalize()).norm() < 1e-4);
+    }
+
+    #[test]
+    fn test_moving_instance() {
+        let cube = Arc::new(Primitive::Cube(Cube::new(Vec3::zeros(), Vec3::repeat(1.))));
+        let instance = Instance::moving(cube, Transform::scale(Vec3::new(2., 1., 1.)), Vec3::new(0., 3., 0.));
+
+        let ray = |time| Ray::with_time(Vec3::new(0., 1.5, 5.), -Vec3::z(), time);
+        assert!(instance.intersect(&ray(0.), (0., std::f32::MAX)).is_none());
+        assert!(instance.intersect(&ray(0.5), (0., std::f32::MAX)).is_some());
+
+        let aabb = instance.aabb();
+        assert!((aabb.min - Vec3::new(-1., -0.5, -0.5)).norm() < 1e-5);
+        assert!((aabb.max - Vec3::new(1., 3.5, 0.5)).norm() < 1e-5);
+    }
+}
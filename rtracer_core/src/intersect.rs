@@ -1,6 +1,34 @@
+use crate::{Vec3, Vec2};
 use crate::ray::Ray;
 use crate::aabb::Aabb;
 
 pub trait Intersect {
     fn intersect(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<f32>;
-}
\ No newline at end of file
+}
+
+// normals are in the space of the ray
+#[derive(Copy, Clone, Debug)]
+pub struct SurfaceHit {
+    pub t: f32,
+    // true normal of the surface
+    pub normal: Vec3,
+    // interpolated from vertex normals, same as normal for analytic primitives
+    pub shading_normal: Vec3,
+    pub uv: Vec2,
+    // interpolated vertex color of meshes
+    pub color: Option<Vec3>,
+}
+
+impl SurfaceHit {
+    pub fn new(t: f32, normal: Vec3) -> SurfaceHit {
+        SurfaceHit { t, normal, shading_normal: normal, uv: Vec2::zeros(), color: None }
+    }
+
+    pub fn with_uv(t: f32, normal: Vec3, uv: Vec2) -> SurfaceHit {
+        SurfaceHit { t, normal, shading_normal: normal, uv, color: None }
+    }
+
+    pub fn with_shading(t: f32, normal: Vec3, shading_normal: Vec3, uv: Vec2) -> SurfaceHit {
+        SurfaceHit { t, normal, shading_normal, uv, color: None }
+    }
+}
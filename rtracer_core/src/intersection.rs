@@ -1,6 +1,13 @@
 use crate::prelude::*;
 use std::mem::swap;
 
+// entry and exit t of the sphere, also behind the ray origin
+pub fn ray_sphere_crossings(ray: &Ray, sphere: &Sphere) -> Option<(f32, f32)> {
+    let oc = ray.origin - sphere.center;
+    solve_quadratic(ray.direction.norm_squared(), Vec3::dot(&ray.direction, &oc),
+                    oc.norm_squared() - sphere.radius * sphere.radius)
+}
+
 pub fn ray_sphere_intersection(ray: &Ray, sphere: &Sphere) -> Option<f32> {
     let oc = ray.origin - sphere.center;
 
@@ -74,10 +81,21 @@ pub fn ray_aabb_intersection(ray: &Ray, aabb: &Aabb) -> Option<(f32, f32)> {
 //        None
 //    }
 
+    let (tmin, tmax) = ray_aabb_crossings(ray, aabb)?;
+    let tmin = tmin.max(0.);
+    if tmax < tmin {
+        return None
+    }
+
+    Some((tmin, tmax))
+}
+
+// slabs without clipping by the ray origin
+pub fn ray_aabb_crossings(ray: &Ray, aabb: &Aabb) -> Option<(f32, f32)> {
     let box_min = aabb.min;
     let box_max = aabb.max;
 
-    let (mut tmin, mut tmax) = (0., std::f32::MAX);
+    let (mut tmin, mut tmax) = (std::f32::MIN, std::f32::MAX);
 
     for a in 0..3 {
         let inv_d = 1. / ray.direction[a];
@@ -90,7 +108,8 @@ pub fn ray_aabb_intersection(ray: &Ray, aabb: &Aabb) -> Option<(f32, f32)> {
 
         tmin = if t0 > tmin { t0 } else { tmin };
         tmax = if t1 < tmax { t1 } else { tmax };
-        if tmax <= tmin {
+        // equal for flat boxes, e.g. bounds of axis aligned triangles
+        if tmax < tmin {
             return None
         }
     }
@@ -99,10 +118,15 @@ pub fn ray_aabb_intersection(ray: &Ray, aabb: &Aabb) -> Option<(f32, f32)> {
 }
 
 pub fn ray_triangle_intersection(ray: &Ray, triangle: &Triangle) -> Option<f32> {
+    moller_trumbore_algorithm(ray, triangle).map(|(t, _, _)| t)
+}
+
+// t with barycentric (u, v) of hit point, weights of v1 and v2
+pub fn ray_triangle_intersection_barycentric(ray: &Ray, triangle: &Triangle) -> Option<(f32, f32, f32)> {
     moller_trumbore_algorithm(ray, triangle)
 }
 
-fn moller_trumbore_algorithm(ray: &Ray, triangle: &Triangle) -> Option<f32> {
+fn moller_trumbore_algorithm(ray: &Ray, triangle: &Triangle) -> Option<(f32, f32, f32)> {
     let e1 = triangle.v1 - triangle.v0;
     let e2 = triangle.v2 - triangle.v0;
 
@@ -126,7 +150,7 @@ fn moller_trumbore_algorithm(ray: &Ray, triangle: &Triangle) -> Option<f32> {
         return None
     }
 
-    Some(Vec3::dot(&e2, &qvec) * inv_det)
+    Some((Vec3::dot(&e2, &qvec) * inv_det, u, v))
 }
 
 pub fn ray_disk_intersection(ray: &Ray, disk: &Disk) -> Option<f32> {
@@ -138,4 +162,268 @@ pub fn ray_disk_intersection(ray: &Ray, disk: &Disk) -> Option<f32> {
     }
 
     None
-}
\ No newline at end of file
+}
+// smallest of crossings within t_min, t_max
+pub fn nearest_crossing(ts: &[Option<f32>], (t_min, t_max): (f32, f32)) -> Option<f32> {
+    ts.iter().filter_map(|t| *t).filter(|t| t_min < *t && *t < t_max).fold(None, |nearest, t| Some(nearest.map_or(t, |n: f32| n.min(t))))
+}
+
+// roots of a t^2 + 2 half_b t + c
+fn solve_quadratic(a: f32, half_b: f32, c: f32) -> Option<(f32, f32)> {
+    if a.abs() < std::f32::EPSILON {
+        return None
+    }
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0. {
+        return None
+    }
+    let root = discriminant.sqrt();
+    Some(((-half_b - root) / a, (-half_b + root) / a))
+}
+
+// hit of disk given by center, unit normal and radius
+fn ray_cap_intersection(ray: &Ray, center: &Vec3, normal: &Vec3, radius: f32) -> Option<f32> {
+    let t = ray_plane_intersection(ray, &Plane { origin: *center, normal: *normal })?;
+    if (ray.point_at_parameter(t) - center).norm_squared() <= radius * radius { Some(t) } else { None }
+}
+
+pub fn ray_cylinder_intersection(ray: &Ray, cylinder: &Cylinder) -> Option<f32> {
+    nearest_crossing(&ray_cylinder_crossings(ray, cylinder), (0., std::f32::MAX))
+}
+
+// every t where the ray crosses the surface, unordered
+pub fn ray_cylinder_crossings(ray: &Ray, cylinder: &Cylinder) -> [Option<f32>; 4] {
+    let axis = cylinder.top - cylinder.base;
+    let height = axis.norm();
+    let axis = axis / height;
+
+    let oc = ray.origin - cylinder.base;
+    let (d_axis, oc_axis) = (Vec3::dot(&ray.direction, &axis), Vec3::dot(&oc, &axis));
+    // directions of rays transformed by instances are not normalized
+    let dd = ray.direction.norm_squared();
+
+    // infinite cylinder, components perpendicular to the axis
+    let side = solve_quadratic(dd - d_axis * d_axis,
+                               Vec3::dot(&ray.direction, &oc) - d_axis * oc_axis,
+                               oc.norm_squared() - oc_axis * oc_axis - cylinder.radius * cylinder.radius);
+    let on_side = |t: f32| {
+        let h = oc_axis + t * d_axis;
+        if h >= 0. && h <= height { Some(t) } else { None }
+    };
+
+    let mut ts = [None; 4];
+    if let Some((t1, t2)) = side {
+        ts[0] = on_side(t1);
+        ts[1] = on_side(t2);
+    }
+    if cylinder.capped {
+        ts[2] = ray_cap_intersection(ray, &cylinder.base, &-axis, cylinder.radius);
+        ts[3] = ray_cap_intersection(ray, &cylinder.top, &axis, cylinder.radius);
+    }
+    ts
+}
+
+pub fn ray_cone_intersection(ray: &Ray, cone: &Cone) -> Option<f32> {
+    nearest_crossing(&ray_cone_crossings(ray, cone), (0., std::f32::MAX))
+}
+
+pub fn ray_cone_crossings(ray: &Ray, cone: &Cone) -> [Option<f32>; 3] {
+    // axis goes from apex to base
+    let axis = cone.base - cone.apex;
+    let height = axis.norm();
+    let axis = axis / height;
+    let cos2 = height * height / (height * height + cone.radius * cone.radius);
+
+    let co = ray.origin - cone.apex;
+    let (d_axis, co_axis) = (Vec3::dot(&ray.direction, &axis), Vec3::dot(&co, &axis));
+    let dd = ray.direction.norm_squared();
+
+    // double cone, only the half between apex and base is kept
+    let side = solve_quadratic(d_axis * d_axis - cos2 * dd,
+                               d_axis * co_axis - cos2 * Vec3::dot(&ray.direction, &co),
+                               co_axis * co_axis - cos2 * co.norm_squared());
+    let on_side = |t: f32| {
+        let h = co_axis + t * d_axis;
+        if h >= 0. && h <= height { Some(t) } else { None }
+    };
+
+    let mut ts = [None; 3];
+    if let Some((t1, t2)) = side {
+        ts[0] = on_side(t1);
+        ts[1] = on_side(t2);
+    }
+    if cone.capped {
+        ts[2] = ray_cap_intersection(ray, &cone.base, &axis, cone.radius);
+    }
+    ts
+}
+
+pub fn ray_capsule_intersection(ray: &Ray, capsule: &Capsule) -> Option<f32> {
+    nearest_crossing(&ray_capsule_crossings(ray, capsule), (0., std::f32::MAX))
+}
+
+pub fn ray_capsule_crossings(ray: &Ray, capsule: &Capsule) -> [Option<f32>; 6] {
+    let axis = capsule.b - capsule.a;
+    let length = axis.norm();
+    let axis = axis / length;
+
+    let oa = ray.origin - capsule.a;
+    let (d_axis, oa_axis) = (Vec3::dot(&ray.direction, &axis), Vec3::dot(&oa, &axis));
+    let r2 = capsule.radius * capsule.radius;
+    let dd = ray.direction.norm_squared();
+
+    let mut ts = [None; 6];
+    if let Some((t1, t2)) = solve_quadratic(dd - d_axis * d_axis,
+                                            Vec3::dot(&ray.direction, &oa) - d_axis * oa_axis,
+                                            oa.norm_squared() - oa_axis * oa_axis - r2) {
+        for (i, t) in [t1, t2].iter().enumerate() {
+            let h = oa_axis + t * d_axis;
+            if h >= 0. && h <= length {
+                ts[i] = Some(*t);
+            }
+        }
+    }
+
+    // end spheres, each one only outside of the segment
+    for (i, (center, outside)) in [(capsule.a, -1.), (capsule.b, 1.)].iter().enumerate() {
+        let oc = ray.origin - center;
+        if let Some((t1, t2)) = solve_quadratic(dd, Vec3::dot(&ray.direction, &oc), oc.norm_squared() - r2) {
+            for (j, t) in [t1, t2].iter().enumerate() {
+                let h = Vec3::dot(&(ray.point_at_parameter(*t) - center), &axis);
+                if h * outside >= 0. {
+                    ts[2 + 2 * i + j] = Some(*t);
+                }
+            }
+        }
+    }
+    ts
+}
+
+pub fn ray_torus_intersection(ray: &Ray, torus: &Torus) -> Option<f32> {
+    nearest_crossing(&ray_torus_crossings(ray, torus), (0., std::f32::MAX))
+}
+
+pub fn ray_torus_crossings(ray: &Ray, torus: &Torus) -> [Option<f32>; 4] {
+    let (major, minor) = (torus.major_radius, torus.minor_radius);
+
+    // quartic is monic for unit direction, roots are scaled back by speed
+    let speed = ray.direction.norm();
+    let direction = ray.direction / speed;
+
+    // start at bounding sphere, quartic loses precision far from the torus
+    let oc = ray.origin - torus.center;
+    let b = Vec3::dot(&direction, &oc);
+    let c = oc.norm_squared() - (major + minor) * (major + minor);
+    if b * b - c < 0. {
+        return [None; 4]
+    }
+    let t0 = -b - (b * b - c).sqrt();
+
+    let o = (oc + t0 * direction).map(f64::from);
+    let d = direction.map(f64::from);
+    let axis = torus.axis.map(f64::from);
+    let (major, minor) = (f64::from(major), f64::from(minor));
+
+    // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (|p|^2 - (p.axis)^2) for p = o + t d
+    let (od, o_axis, d_axis) = (o.dot(&d), o.dot(&axis), d.dot(&axis));
+    let k = o.norm_squared() + major * major - minor * minor;
+    let r2 = 4. * major * major;
+    let coeffs = [
+        4. * od,
+        4. * od * od + 2. * k - r2 * (1. - d_axis * d_axis),
+        4. * od * k - 2. * r2 * (od - o_axis * d_axis),
+        k * k - r2 * (o.norm_squared() - o_axis * o_axis),
+    ];
+
+    // relative to the original origin
+    let mut ts = [None; 4];
+    for (t, root) in ts.iter_mut().zip(solve_quartic(coeffs).iter()) {
+        *t = root.map(|root| (root as f32 + t0) / speed);
+    }
+    ts
+}
+
+// real roots of monic t^4 + c[0] t^3 + c[1] t^2 + c[2] t + c[3], ferrari method, polished by newton steps
+fn solve_quartic(c: [f64; 4]) -> [Option<f64>; 4] {
+    let [a, b, c1, d] = c;
+    // t = y - a / 4 gives y^4 + p y^2 + q y + r
+    let p = b - 3. * a * a / 8.;
+    let q = c1 - a * b / 2. + a * a * a / 8.;
+    let r = d - a * c1 / 4. + a * a * b / 16. - 3. * a * a * a * a / 256.;
+
+    // roots of x^2 + b x + c stored at offset
+    fn push_quadratic(roots: &mut [Option<f64>; 4], offset: usize, b: f64, c: f64) {
+        let disc = b * b - 4. * c;
+        if disc >= 0. {
+            let s = disc.sqrt();
+            roots[offset] = Some((-b - s) / 2.);
+            roots[offset + 1] = Some((-b + s) / 2.);
+        }
+    }
+
+    let mut roots = [None; 4];
+
+    if q.abs() < 1e-12 {
+        // biquadratic
+        let mut z = [None; 4];
+        push_quadratic(&mut z, 0, p, r);
+        for (i, z) in z.iter().take(2).enumerate() {
+            if let Some(z) = z.filter(|z| *z >= 0.) {
+                roots[2 * i] = Some(z.sqrt());
+                roots[2 * i + 1] = Some(-z.sqrt());
+            }
+        }
+    } else {
+        // positive root of resolvent cubic m^3 + p m^2 + (p^2 / 4 - r) m - q^2 / 8
+        let m = cubic_largest_root(p, p * p / 4. - r, -q * q / 8.);
+        if m <= 0. {
+            return roots
+        }
+        let s = (2. * m).sqrt();
+        push_quadratic(&mut roots, 0, s, p / 2. + m - q / (2. * s));
+        push_quadratic(&mut roots, 2, -s, p / 2. + m + q / (2. * s));
+    }
+
+    let f = |t: f64| (((t + a) * t + b) * t + c1) * t + d;
+    let df = |t: f64| ((4. * t + 3. * a) * t + 2. * b) * t + c1;
+    for root in roots.iter_mut() {
+        if let Some(y) = root {
+            let mut t = *y - a / 4.;
+            for _ in 0..2 {
+                let slope = df(t);
+                if slope != 0. {
+                    t -= f(t) / slope;
+                }
+            }
+            *root = Some(t);
+        }
+    }
+    roots
+}
+
+// largest real root of monic x^3 + b x^2 + c x + d
+fn cubic_largest_root(b: f64, c: f64, d: f64) -> f64 {
+    let p = c - b * b / 3.;
+    let q = 2. * b * b * b / 27. - b * c / 3. + d;
+    let disc = q * q / 4. + p * p * p / 27.;
+
+    let y = if disc > 0. {
+        let s = disc.sqrt();
+        (-q / 2. + s).cbrt() + (-q / 2. - s).cbrt()
+    } else if p < 0. {
+        let k = 2. * (-p / 3.).sqrt();
+        let phi = (3. * q / (p * k)).clamp(-1., 1.).acos();
+        k * (phi / 3.).cos()
+    } else {
+        0.
+    };
+
+    let mut x = y - b / 3.;
+    for _ in 0..2 {
+        let slope = (3. * x + 2. * b) * x + c;
+        if slope != 0. {
+            x -= (((x + b) * x + c) * x + d) / slope;
+        }
+    }
+    x
+}
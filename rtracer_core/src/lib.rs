@@ -6,7 +6,9 @@ extern crate nalgebra as na;
 pub mod prelude;
 
 pub mod image;
+pub mod film;
 pub mod intersection;
+pub mod bvh;
 
 pub type Vec3 = na::Vector3<f32>;
 pub type Vec2 = na::Vector2<f32>;
@@ -24,10 +26,23 @@ mod plane;
 mod cube;
 mod triangle;
 mod disk;
+mod cylinder;
+mod cone;
+mod capsule;
+mod torus;
+mod sdf;
+mod csg;
+mod transform;
+mod instance;
+mod mesh;
 mod primitive;
 mod object;
 mod camera;
 mod material;
+mod texture;
+mod environment;
+mod sky;
 mod scene_data;
 pub mod model_loader;
+pub mod scene_file;
 mod bounded;
@@ -1,32 +1,35 @@
 use crate::Vec3;
+use crate::texture::Texture;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum Material {
     Lambertian(Lambertian),
     Metal(Metal),
     Dielectric(Dielectric),
+    Emissive(Emissive),
+    Microfacet(Microfacet),
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Lambertian {
-    pub albedo: Vec3,
+    pub albedo: Texture,
 }
 
 impl Lambertian {
-    pub fn new(albedo: Vec3) -> Lambertian {
-        Lambertian { albedo }
+    pub fn new<T: Into<Texture>>(albedo: T) -> Lambertian {
+        Lambertian { albedo: albedo.into() }
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Metal {
-    pub albedo: Vec3,
+    pub albedo: Texture,
     pub fuzz: f32,
 }
 
 impl Metal {
-    pub fn new(albedo: Vec3, fuzz: f32) -> Metal {
-        Metal { albedo, fuzz: fuzz.min(1.).max(0.) }
+    pub fn new<T: Into<Texture>>(albedo: T, fuzz: f32) -> Metal {
+        Metal { albedo: albedo.into(), fuzz: fuzz.min(1.).max(0.) }
     }
 }
 
@@ -42,6 +45,49 @@ impl Dielectric {
     }
 }
 
+#[derive(Clone, Copy)]
+pub struct Emissive {
+    pub radiance: Vec3,
+}
+
+impl Emissive {
+    pub fn new(radiance: Vec3) -> Emissive {
+        Emissive { radiance }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fresnel {
+    // schlick approximation, reflectance at normal incidence is albedo
+    Schlick,
+    // complex index of refraction of metal per rgb channel, albedo tints reflection
+    Conductor { eta: Vec3, k: Vec3 },
+    // glossy dielectric coat over diffuse base colored by albedo
+    Dielectric { ior: f32 },
+}
+
+// ggx microfacet surface, roughness is perceptual like in gltf and most dcc tools
+#[derive(Clone)]
+pub struct Microfacet {
+    pub albedo: Texture,
+    pub roughness: f32,
+    pub fresnel: Fresnel,
+}
+
+impl Microfacet {
+    pub fn new<T: Into<Texture>>(albedo: T, roughness: f32, fresnel: Fresnel) -> Microfacet {
+        Microfacet { albedo: albedo.into(), roughness: roughness.min(1.).max(0.), fresnel }
+    }
+
+    pub fn metal<T: Into<Texture>>(albedo: T, roughness: f32) -> Microfacet {
+        Microfacet::new(albedo, roughness, Fresnel::Schlick)
+    }
+
+    pub fn dielectric<T: Into<Texture>>(albedo: T, roughness: f32, ior: f32) -> Microfacet {
+        Microfacet::new(albedo, roughness, Fresnel::Dielectric { ior })
+    }
+}
+
 impl From<Lambertian> for Material {
     fn from(l: Lambertian) -> Self {
         Material::Lambertian(l)
@@ -59,3 +105,15 @@ impl From<Dielectric> for Material {
         Material::Dielectric(d)
     }
 }
+
+impl From<Emissive> for Material {
+    fn from(e: Emissive) -> Self {
+        Material::Emissive(e)
+    }
+}
+
+impl From<Microfacet> for Material {
+    fn from(m: Microfacet) -> Self {
+        Material::Microfacet(m)
+    }
+}
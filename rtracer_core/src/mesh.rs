@@ -0,0 +1,283 @@
+use std::sync::Arc;
+
+use crate::{Vec3, Vec2};
+use crate::ray::Ray;
+use crate::aabb::Aabb;
+use crate::bounded::Bounded;
+use crate::intersect::{Intersect, SurfaceHit};
+use crate::triangle::Triangle;
+use crate::bvh::{BvhBuilder, BvhBuildNode};
+
+// flattened in depth first order, left child is the next node
+#[derive(Debug)]
+struct MeshBvhNode {
+    aabb: Aabb,
+    // first triangle for leafs, right child for inner nodes
+    offset: u32,
+    // zero for inner nodes
+    count: u32,
+}
+
+// optional per vertex data, every present attribute has the same length as positions
+#[derive(Clone, Debug, Default)]
+pub struct VertexAttributes {
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<Vec2>>,
+    // linear rgb
+    pub colors: Option<Vec<Vec3>>,
+}
+
+#[derive(Debug)]
+struct MeshData {
+    positions: Vec<Vec3>,
+    indices: Vec<[u32; 3]>,
+    attributes: VertexAttributes,
+    nodes: Vec<MeshBvhNode>,
+}
+
+// vertex data and bvh are shared between clones
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    data: Arc<MeshData>,
+}
+
+struct MeshTriangle {
+    aabb: Aabb,
+    indices: [u32; 3],
+}
+
+impl Bounded for MeshTriangle {
+    fn aabb(&self) -> Aabb {
+        self.aabb
+    }
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Vec3>, indices: Vec<[u32; 3]>) -> Mesh {
+        Mesh::with_attributes(positions, indices, VertexAttributes::default())
+    }
+
+    pub fn with_attributes(positions: Vec<Vec3>, indices: Vec<[u32; 3]>, attributes: VertexAttributes) -> Mesh {
+        debug_assert!(indices.iter().all(|tri| tri.iter().all(|i| (*i as usize) < positions.len())));
+        debug_assert!(attributes.normals.as_ref().map_or(true, |n| n.len() == positions.len()));
+        debug_assert!(attributes.uvs.as_ref().map_or(true, |uv| uv.len() == positions.len()));
+        debug_assert!(attributes.colors.as_ref().map_or(true, |c| c.len() == positions.len()));
+
+        let mut triangles: Vec<_> = indices.iter()
+            .map(|tri| MeshTriangle { aabb: triangle_of(&positions, tri).aabb(), indices: *tri })
+            .collect();
+
+        let mut nodes = vec![];
+        if !triangles.is_empty() {
+            let root = BvhBuilder::default().build(&mut triangles);
+            flatten(&root, &mut nodes);
+        }
+
+        let indices = triangles.iter().map(|tri| tri.indices).collect();
+
+        Mesh { data: Arc::new(MeshData { positions, indices, attributes, nodes }) }
+    }
+
+    pub fn positions(&self) -> &[Vec3] {
+        &self.data.positions
+    }
+
+    // order differs from the one passed to constructor, triangles are sorted by bvh
+    pub fn indices(&self) -> &[[u32; 3]] {
+        &self.data.indices
+    }
+
+    pub fn normals(&self) -> Option<&[Vec3]> {
+        self.data.attributes.normals.as_ref().map(|n| n.as_slice())
+    }
+
+    pub fn uvs(&self) -> Option<&[Vec2]> {
+        self.data.attributes.uvs.as_ref().map(|uv| uv.as_slice())
+    }
+
+    pub fn colors(&self) -> Option<&[Vec3]> {
+        self.data.attributes.colors.as_ref().map(|c| c.as_slice())
+    }
+
+    pub fn triangles_count(&self) -> usize {
+        self.data.indices.len()
+    }
+
+    pub fn triangle(&self, idx: usize) -> Triangle {
+        triangle_of(&self.data.positions, &self.data.indices[idx])
+    }
+
+    pub fn hit_surface(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<SurfaceHit> {
+        let (t, idx, u, v) = self.closest_triangle(ray, t_min_max)?;
+        let [i0, i1, i2] = self.data.indices[idx];
+        let (i0, i1, i2) = (i0 as usize, i1 as usize, i2 as usize);
+        let w = 1. - u - v;
+
+        let mut normal = self.triangle(idx).normal();
+
+        let shading_normal = match self.normals() {
+            Some(normals) => {
+                let n = w * normals[i0] + u * normals[i1] + v * normals[i2];
+                if n.norm_squared() > 0. {
+                    let n = n.normalize();
+                    // winding may disagree with vertex normals, they are trusted more
+                    if Vec3::dot(&n, &normal) < 0. {
+                        normal = -normal;
+                    }
+                    n
+                } else {
+                    normal
+                }
+            },
+            None => normal,
+        };
+
+        let uv = match self.uvs() {
+            Some(uvs) => w * uvs[i0] + u * uvs[i1] + v * uvs[i2],
+            None => Vec2::new(u, v),
+        };
+
+        let mut hit = SurfaceHit::with_shading(t, normal, shading_normal, uv);
+        hit.color = self.colors().map(|c| w * c[i0] + u * c[i1] + v * c[i2]);
+        Some(hit)
+    }
+
+    // t, triangle and barycentric (u, v) of the closest hit
+    fn closest_triangle(&self, ray: &Ray, (t_min, t_max): (f32, f32)) -> Option<(f32, usize, f32, f32)> {
+        let nodes = &self.data.nodes;
+        if nodes.is_empty() {
+            return None
+        }
+
+        let mut closest: Option<(f32, usize, f32, f32)> = None;
+        let mut stack = vec![0usize];
+
+        while let Some(idx) = stack.pop() {
+            let node = &nodes[idx];
+            let t_max = closest.map_or(t_max, |(t, _, _, _)| t);
+            if !node.aabb.overlaps_ray(ray, (t_min, t_max)) {
+                continue
+            }
+
+            if node.count == 0 {
+                stack.push(node.offset as usize);
+                stack.push(idx + 1);
+                continue
+            }
+
+            for tri in node.offset as usize..(node.offset + node.count) as usize {
+                let t_max = closest.map_or(t_max, |(t, _, _, _)| t);
+                if let Some((t, u, v)) = self.triangle(tri).intersect_barycentric(ray, (t_min, t_max)) {
+                    closest = Some((t, tri, u, v));
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+impl Intersect for Mesh {
+    fn intersect(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<f32> {
+        self.closest_triangle(ray, t_min_max).map(|(t, _, _, _)| t)
+    }
+}
+
+impl Bounded for Mesh {
+    fn aabb(&self) -> Aabb {
+        self.data.nodes.first().map_or_else(Aabb::empty, |root| root.aabb)
+    }
+}
+
+fn triangle_of(positions: &[Vec3], indices: &[u32; 3]) -> Triangle {
+    Triangle::new(positions[indices[0] as usize], positions[indices[1] as usize], positions[indices[2] as usize])
+}
+
+fn flatten(node: &BvhBuildNode, nodes: &mut Vec<MeshBvhNode>) {
+    match node {
+        BvhBuildNode::Leaf { aabb, objs } => {
+            nodes.push(MeshBvhNode { aabb: *aabb, offset: objs.start as u32, count: objs.len() as u32 });
+        },
+        BvhBuildNode::Node { aabb, left, right } => {
+            let idx = nodes.len();
+            nodes.push(MeshBvhNode { aabb: *aabb, offset: 0, count: 0 });
+            flatten(left, nodes);
+            nodes[idx].offset = nodes.len() as u32;
+            flatten(right, nodes);
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // n x n quads in z = 0 plane, two triangles each
+    fn grid(n: u32) -> Mesh {
+        let mut positions = vec![];
+        for y in 0..=n {
+            for x in 0..=n {
+                positions.push(Vec3::new(x as f32, y as f32, 0.));
+            }
+        }
+
+        let mut indices = vec![];
+        for y in 0..n {
+            for x in 0..n {
+                let i = y * (n + 1) + x;
+                indices.push([i, i + 1, i + n + 2]);
+                indices.push([i, i + n + 2, i + n + 1]);
+            }
+        }
+
+        Mesh::new(positions, indices)
+    }
+
+    #[test]
+    fn test_mesh_matches_brute_force() {
+        let mesh = grid(8);
+        assert_eq!(mesh.triangles_count(), 128);
+
+        for i in 0..64 {
+            let origin = Vec3::new(i as f32 * 0.13, (i * 7 % 9) as f32 - 0.5, 3.);
+            let ray = Ray::new(origin, Vec3::new(0.05, 0.1, -1.));
+
+            let brute = (0..mesh.triangles_count())
+                .filter_map(|tri| mesh.triangle(tri).intersect(&ray, (0., std::f32::MAX)))
+                .fold(None, |closest: Option<f32>, t| Some(closest.map_or(t, |c| c.min(t))));
+
+            assert_eq!(mesh.intersect(&ray, (0., std::f32::MAX)), brute);
+        }
+    }
+
+    #[test]
+    fn test_mesh_aabb_and_normal() {
+        let mesh = grid(4);
+        let aabb = mesh.aabb();
+        assert_eq!(aabb.min, Vec3::zeros());
+        assert_eq!(aabb.max, Vec3::new(4., 4., 0.));
+
+        let hit = mesh.hit_surface(&Ray::new(Vec3::new(1.5, 2.5, 1.), -Vec3::z()), (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(hit.t, 1.));
+        assert_eq!(hit.normal, Vec3::z());
+    }
+
+    #[test]
+    fn test_interpolated_attributes() {
+        let positions = vec![Vec3::new(0., 0., 0.), Vec3::new(1., 0., 0.), Vec3::new(0., 1., 0.)];
+        let normals = vec![Vec3::new(-1., 0., 1.).normalize(), Vec3::new(1., 0., 1.).normalize(), Vec3::z()];
+        let uvs = vec![Vec2::new(0., 0.), Vec2::new(1., 0.), Vec2::new(0., 1.)];
+        // clockwise winding, geometric normal is flipped to the side of vertex normals
+        let colors = vec![Vec3::x(), Vec3::y(), Vec3::z()];
+        let attributes = VertexAttributes { normals: Some(normals), uvs: Some(uvs), colors: Some(colors) };
+        let mesh = Mesh::with_attributes(positions, vec![[0, 2, 1]], attributes);
+
+        let hit = mesh.hit_surface(&Ray::new(Vec3::new(0.5, 0.25, 1.), -Vec3::z()), (0., std::f32::MAX)).unwrap();
+        assert_eq!(hit.normal, Vec3::z());
+        assert!(hit.shading_normal.x > 0.);
+        assert!(relative_eq!(hit.shading_normal.norm(), 1., epsilon = 1e-6));
+        assert!(relative_eq!(hit.uv.x, 0.5, epsilon = 1e-6));
+        assert!(relative_eq!(hit.uv.y, 0.25, epsilon = 1e-6));
+        assert!((hit.color.unwrap() - Vec3::new(0.25, 0.5, 0.25)).norm() < 1e-6);
+    }
+}
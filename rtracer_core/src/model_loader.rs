@@ -1,11 +1,125 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::path::Path;
+use std::sync::Arc;
 use crate::triangle::Triangle;
-use crate::Vec3;
-use crate::material::Material;
-use crate::scene_data::SceneObject;
+use crate::{Vec3, Vec2, Mat4};
+use crate::mesh::{Mesh, VertexAttributes};
+use crate::primitive::Primitive;
+use crate::instance::Instance;
+use crate::transform::Transform;
+use crate::camera::{Camera, Projection};
+use crate::material::{Material, Lambertian, Metal, Dielectric, Emissive, Microfacet};
+use crate::scene_data::{SceneData, MaterialId, ObjectId};
 
-pub fn load_geometry_obj(path: &Path) -> Result<Vec<Triangle>, String>{
-    let (mut models, _) = tobj::load_obj(path).unwrap();
+mod ply;
+mod stl;
+
+#[derive(Debug)]
+pub enum ModelError {
+    Io(std::io::Error),
+    Obj(tobj::LoadError),
+    Gltf(gltf::Error),
+    // file is readable, but its content is not valid
+    Malformed { format: &'static str, reason: String },
+    UnknownFormat(String),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::Io(err) => write!(f, "cant read model: {}", err),
+            ModelError::Obj(err) => write!(f, "invalid obj file: {}", err),
+            ModelError::Gltf(err) => write!(f, "invalid gltf file: {}", err),
+            ModelError::Malformed { format, reason } => write!(f, "malformed {} file: {}", format, reason),
+            ModelError::UnknownFormat(path) => write!(f, "unknown model format of '{}'", path),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+impl From<std::io::Error> for ModelError {
+    fn from(err: std::io::Error) -> Self {
+        ModelError::Io(err)
+    }
+}
+
+impl From<tobj::LoadError> for ModelError {
+    fn from(err: tobj::LoadError) -> Self {
+        ModelError::Obj(err)
+    }
+}
+
+impl From<gltf::Error> for ModelError {
+    fn from(err: gltf::Error) -> Self {
+        ModelError::Gltf(err)
+    }
+}
+
+// vertex data parsed from file, before bvh is built
+struct MeshBuffers {
+    positions: Vec<Vec3>,
+    indices: Vec<[u32; 3]>,
+    attributes: VertexAttributes,
+}
+
+impl MeshBuffers {
+    fn triangles(&self) -> Vec<Triangle> {
+        let p = &self.positions;
+        self.indices.iter()
+            .map(|[i0, i1, i2]| Triangle::new(p[*i0 as usize], p[*i1 as usize], p[*i2 as usize]))
+            .collect()
+    }
+
+    fn into_mesh(self) -> Mesh {
+        Mesh::with_attributes(self.positions, self.indices, self.attributes)
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase())
+}
+
+// format is chosen by file extension
+pub fn load_geometry(path: &Path) -> Result<Vec<Triangle>, ModelError> {
+    match extension(path).as_deref() {
+        Some("obj") => load_geometry_obj(path),
+        Some("ply") => load_geometry_ply(path),
+        Some("stl") => load_geometry_stl(path),
+        _ => Err(ModelError::UnknownFormat(path.display().to_string())),
+    }
+}
+
+// format is chosen by file extension, only obj files may have several meshes
+pub fn load_mesh(path: &Path) -> Result<Vec<Mesh>, ModelError> {
+    match extension(path).as_deref() {
+        Some("obj") => load_mesh_obj(path),
+        Some("ply") => Ok(vec![load_mesh_ply(path)?]),
+        Some("stl") => Ok(vec![load_mesh_stl(path)?]),
+        _ => Err(ModelError::UnknownFormat(path.display().to_string())),
+    }
+}
+
+pub fn load_geometry_ply(path: &Path) -> Result<Vec<Triangle>, ModelError> {
+    Ok(ply::parse(&std::fs::read(path)?)?.triangles())
+}
+
+// vertex normals, uvs and colors are kept when present
+pub fn load_mesh_ply(path: &Path) -> Result<Mesh, ModelError> {
+    Ok(ply::parse(&std::fs::read(path)?)?.into_mesh())
+}
+
+pub fn load_geometry_stl(path: &Path) -> Result<Vec<Triangle>, ModelError> {
+    Ok(stl::parse(&std::fs::read(path)?)?.triangles())
+}
+
+pub fn load_mesh_stl(path: &Path) -> Result<Mesh, ModelError> {
+    Ok(stl::parse(&std::fs::read(path)?)?.into_mesh())
+}
+
+pub fn load_geometry_obj(path: &Path) -> Result<Vec<Triangle>, ModelError> {
+    let (models, _) = tobj::load_obj(path)?;
 
     let mut ts = vec![];
 
@@ -33,3 +147,512 @@ pub fn load_geometry_obj(path: &Path) -> Result<Vec<Triangle>, String>{
 
     Ok(ts)
 }
+
+pub struct ObjModel {
+    pub mesh: Mesh,
+    // index into materials loaded from mtl
+    pub material: Option<usize>,
+}
+
+// one mesh for every model of the file, vertices are shared between triangles
+pub fn load_mesh_obj(path: &Path) -> Result<Vec<Mesh>, ModelError> {
+    let (models, _) = load_obj_with_materials(path)?;
+    Ok(models.into_iter().map(|model| model.mesh).collect())
+}
+
+// tobj splits models by material, so every model has at most one material
+pub fn load_obj_with_materials(path: &Path) -> Result<(Vec<ObjModel>, Vec<Material>), ModelError> {
+    let (models, materials) = tobj::load_obj(path)?;
+
+    let models = models.into_iter()
+        .filter(|model| !model.mesh.indices.is_empty())
+        .map(|model| ObjModel { material: model.mesh.material_id, mesh: mesh_from_obj(&model.mesh) })
+        .collect();
+
+    let materials = materials.iter().map(material_from_mtl).collect();
+
+    Ok((models, materials))
+}
+
+// registers mtl materials in scene, models without material get default one
+pub fn add_obj_to_scene(scene: &mut SceneData, path: &Path, default_material: MaterialId) -> Result<Vec<ObjectId>, ModelError> {
+    let (models, materials) = load_obj_with_materials(path)?;
+
+    let material_ids: Vec<_> = materials.into_iter().map(|m| scene.add_material(m)).collect();
+
+    let mut objects = vec![];
+    for model in models {
+        let material = model.material.and_then(|idx| material_ids.get(idx).cloned()).unwrap_or(default_material);
+        let primitive = scene.add_primitive(model.mesh.into());
+        if let Some(object) = scene.add_object(primitive, material) {
+            objects.push(object);
+        }
+    }
+
+    Ok(objects)
+}
+
+fn mesh_from_obj(mesh: &tobj::Mesh) -> Mesh {
+    let positions = mesh.positions.chunks(3).map(|p| Vec3::new(p[0], p[1], p[2])).collect();
+    let indices = mesh.indices.chunks(3).map(|i| [i[0], i[1], i[2]]).collect();
+
+    let normals = if mesh.normals.is_empty() {
+        None
+    } else {
+        Some(mesh.normals.chunks(3).map(|n| Vec3::new(n[0], n[1], n[2])).collect())
+    };
+
+    let uvs = if mesh.texcoords.is_empty() {
+        None
+    } else {
+        Some(mesh.texcoords.chunks(2).map(|uv| Vec2::new(uv[0], uv[1])).collect())
+    };
+
+    Mesh::with_attributes(positions, indices, VertexAttributes { normals, uvs, colors: None })
+}
+
+// illum models: 3, 5 - reflection, 4, 6, 7, 9 - refraction
+pub fn material_from_mtl(m: &tobj::Material) -> Material {
+    let kd = Vec3::from(m.diffuse);
+    let ks = Vec3::from(m.specular);
+
+    // Ke is not known to tobj
+    let ke = m.unknown_param.get("Ke").and_then(|ke| parse_vec3(ke)).unwrap_or_else(Vec3::zeros);
+    if ke.max() > 0. {
+        return Emissive::new(ke).into()
+    }
+
+    let illum = m.illumination_model.unwrap_or(2);
+
+    if m.dissolve < 1. || [4, 6, 7, 9].contains(&illum) {
+        let ref_idx = if m.optical_density > 1. { m.optical_density } else { 1.5 };
+        return Dielectric::new(Vec3::repeat(1.), ref_idx).into()
+    }
+
+    if illum == 3 || illum == 5 || (ks.max() > 0. && kd.max() == 0.) {
+        // rough approximation of phong exponent by fuzz
+        let fuzz = (2. / (m.shininess.max(0.) + 2.)).sqrt();
+        return Metal::new(ks, fuzz).into()
+    }
+
+    Lambertian::new(kd).into()
+}
+
+fn parse_vec3(s: &str) -> Option<Vec3> {
+    let v: Vec<f32> = s.split_whitespace().map(|x| x.parse()).collect::<Result<_, _>>().ok()?;
+    match v.len() {
+        1 => Some(Vec3::repeat(v[0])),
+        3 => Some(Vec3::new(v[0], v[1], v[2])),
+        _ => None,
+    }
+}
+
+// fills scene with meshes of the default glTF scene placed by root transform, returns its first camera
+// camera aspect is taken from the file when present
+pub fn load_gltf(scene: &mut SceneData, path: &Path, root: &Transform, default_aspect: f32) -> Result<Option<Camera>, ModelError> {
+    let (document, buffers, _) = gltf::import(path)?;
+
+    let gltf_scene = document.default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| malformed_gltf("file without scenes".to_string()))?;
+
+    let mut loader = GltfLoader {
+        buffers: &buffers,
+        materials: HashMap::new(),
+        meshes: HashMap::new(),
+        camera: None,
+        default_aspect,
+    };
+
+    for node in gltf_scene.nodes() {
+        loader.add_node(scene, &node, root.matrix())?;
+    }
+
+    Ok(loader.camera)
+}
+
+struct GltfLoader<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    // None key is the default material of the spec
+    materials: HashMap<Option<usize>, MaterialId>,
+    // primitives are shared by all nodes using the mesh
+    meshes: HashMap<usize, Vec<(Arc<Primitive>, Option<usize>)>>,
+    camera: Option<Camera>,
+    default_aspect: f32,
+}
+
+impl<'a> GltfLoader<'a> {
+    fn add_node(&mut self, scene: &mut SceneData, node: &gltf::Node, parent: &Mat4) -> Result<(), ModelError> {
+        let matrix = parent * Mat4::from(node.transform().matrix());
+
+        if let (None, Some(camera)) = (&self.camera, node.camera()) {
+            self.camera = Some(self.camera_from_gltf(&camera, &matrix));
+        }
+
+        if let Some(mesh) = node.mesh() {
+            let transform = Transform::new(matrix)
+                .ok_or_else(|| malformed_gltf(format!("node {} has degenerate transform", node.index())))?;
+
+            for (primitive, material) in self.mesh(&mesh)? {
+                let material = self.material(scene, &mesh, material);
+                let primitive = if matrix == Mat4::identity() {
+                    (*primitive).clone()
+                } else {
+                    Instance::new(primitive, transform).into()
+                };
+                let primitive = scene.add_primitive(primitive);
+                scene.add_object(primitive, material);
+            }
+        }
+
+        for child in node.children() {
+            self.add_node(scene, &child, &matrix)?;
+        }
+
+        Ok(())
+    }
+
+    fn mesh(&mut self, mesh: &gltf::Mesh) -> Result<Vec<(Arc<Primitive>, Option<usize>)>, ModelError> {
+        if let Some(primitives) = self.meshes.get(&mesh.index()) {
+            return Ok(primitives.clone())
+        }
+
+        let mut primitives = vec![];
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue
+            }
+
+            let buffers = self.buffers;
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+            let positions: Vec<_> = reader.read_positions()
+                .ok_or_else(|| malformed_gltf(format!("mesh {} without positions", mesh.index())))?
+                .map(Vec3::from)
+                .collect();
+
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            if indices.len() < 3 {
+                continue
+            }
+            if indices.iter().any(|i| *i as usize >= positions.len()) {
+                return Err(malformed_gltf(format!("mesh {} has index out of range", mesh.index())))
+            }
+
+            let attributes = VertexAttributes {
+                normals: reader.read_normals().map(|n| n.map(Vec3::from).collect()),
+                uvs: reader.read_tex_coords(0).map(|uv| uv.into_f32().map(Vec2::from).collect()),
+                colors: reader.read_colors(0).map(|c| c.into_rgb_f32().map(Vec3::from).collect()),
+            };
+
+            let indices = indices.chunks_exact(3).map(|i| [i[0], i[1], i[2]]).collect();
+            let geometry = Mesh::with_attributes(positions, indices, attributes);
+
+            primitives.push((Arc::new(geometry.into()), primitive.material().index()));
+        }
+
+        self.meshes.insert(mesh.index(), primitives.clone());
+        Ok(primitives)
+    }
+
+    fn material(&mut self, scene: &mut SceneData, mesh: &gltf::Mesh, index: Option<usize>) -> MaterialId {
+        if let Some(id) = self.materials.get(&index) {
+            return *id
+        }
+
+        let material = mesh.primitives()
+            .map(|p| p.material())
+            .find(|m| m.index() == index)
+            .map_or_else(|| Lambertian::new(Vec3::repeat(1.)).into(), |m| material_from_gltf(&m));
+
+        let id = scene.add_material(material);
+        self.materials.insert(index, id);
+        id
+    }
+
+    // glTF camera looks along -z with y up
+    fn camera_from_gltf(&self, camera: &gltf::Camera, matrix: &Mat4) -> Camera {
+        let transform = Transform::new(*matrix).unwrap_or_default();
+
+        let lookfrom = transform.transform_point(&Vec3::zeros());
+        let lookat = lookfrom + transform.transform_vector(&-Vec3::z()).normalize();
+        let vup = transform.transform_vector(&Vec3::y()).normalize();
+
+        let (vfov, aspect, projection) = match camera.projection() {
+            gltf::camera::Projection::Perspective(p) => (p.yfov().to_degrees(), p.aspect_ratio(), Projection::Perspective),
+            // magnifications are half sizes of the view
+            gltf::camera::Projection::Orthographic(o) => {
+                (90., Some(o.xmag() / o.ymag()), Projection::Orthographic { height: 2. * o.ymag() })
+            },
+        };
+
+        let mut camera = Camera::new(lookfrom, lookat, vup, vfov, aspect.unwrap_or(self.default_aspect));
+        camera.projection = projection;
+        camera
+    }
+}
+
+fn malformed_gltf(reason: String) -> ModelError {
+    ModelError::Malformed { format: "gltf", reason }
+}
+
+// metallic roughness model mapped on our materials, textures are ignored
+// metallic factor is not blended, surfaces are either metal or dielectric
+pub fn material_from_gltf(m: &gltf::Material) -> Material {
+    let emissive = Vec3::from(m.emissive_factor());
+    if emissive.max() > 0. {
+        return Emissive::new(emissive).into()
+    }
+
+    let pbr = m.pbr_metallic_roughness();
+    let [r, g, b, alpha] = pbr.base_color_factor();
+    let base_color = Vec3::new(r, g, b);
+
+    if m.alpha_mode() == gltf::material::AlphaMode::Blend && alpha < 1. {
+        return Dielectric::new(base_color, 1.5).into()
+    }
+
+    if pbr.metallic_factor() >= 0.5 {
+        return Microfacet::metal(base_color, pbr.roughness_factor()).into()
+    }
+
+    // gltf dielectrics have ior of 1.5
+    Microfacet::dielectric(base_color, pbr.roughness_factor(), 1.5).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounded::Bounded;
+    use crate::ray::Ray;
+
+    const OBJ: &str = "mtllib quads.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+usemtl red
+f 1 2 3
+usemtl lamp
+f 1 3 4
+";
+
+    const MTL: &str = "newmtl red
+Kd 0.8 0.1 0.1
+illum 2
+
+newmtl lamp
+Kd 0 0 0
+Ke 4 4 4
+";
+
+    #[test]
+    fn test_obj_materials() {
+        let dir = std::env::temp_dir().join("rtracer_model_loader_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("quads.obj"), OBJ).unwrap();
+        std::fs::write(dir.join("quads.mtl"), MTL).unwrap();
+
+        let mut scene = SceneData::new();
+        let default_material = scene.add_material(Lambertian::new(Vec3::repeat(0.5)).into());
+        let objects = add_obj_to_scene(&mut scene, &dir.join("quads.obj"), default_material).unwrap();
+
+        assert_eq!(objects.len(), 2);
+        assert_eq!(scene.materials_count(), 3);
+
+        let materials: Vec<_> = objects.iter()
+            .map(|id| scene.material(scene.object(*id).unwrap().material()).unwrap())
+            .collect();
+        match materials[0] {
+            Material::Lambertian(l) => assert_eq!(l.albedo.average(), Vec3::new(0.8, 0.1, 0.1)),
+            _ => panic!("expected lambertian"),
+        }
+        match materials[1] {
+            Material::Emissive(e) => assert_eq!(e.radiance, Vec3::repeat(4.)),
+            _ => panic!("expected emissive"),
+        }
+    }
+
+    // one triangle mesh used by two nodes, second one is child of translated parent
+    const GLTF: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 1, 3] }],
+        "nodes": [
+            { "mesh": 0 },
+            { "translation": [0, 0, -2], "children": [2] },
+            { "mesh": 0, "scale": [2, 2, 2] },
+            { "camera": 0, "translation": [0, 0, 5] }
+        ],
+        "cameras": [{ "type": "perspective", "perspective": { "yfov": 0.5, "aspectRatio": 2.0, "znear": 0.1 } }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 }] }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [1, 0.5, 0.5, 1], "metallicFactor": 1, "roughnessFactor": 0.2 } }],
+        "buffers": [{ "byteLength": 44, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA=" }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ]
+    }"#;
+
+    #[test]
+    fn test_gltf_scene() {
+        let dir = std::env::temp_dir().join("rtracer_model_loader_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("triangle.gltf"), GLTF).unwrap();
+
+        let mut scene = SceneData::new();
+        let camera = load_gltf(&mut scene, &dir.join("triangle.gltf"), &Transform::identity(), 1.).unwrap().unwrap();
+
+        assert_eq!(scene.objects_count(), 2);
+        assert_eq!(scene.materials_count(), 1);
+        assert_eq!(camera.lookfrom, Vec3::new(0., 0., 5.));
+        assert_eq!(camera.aspect, 2.);
+
+        let aabbs: Vec<_> = scene.to_objects().iter().map(|o| o.primitive.aabb()).collect();
+        assert_eq!(aabbs[0].max, Vec3::new(1., 1., 0.));
+        assert_eq!(aabbs[1].min, Vec3::new(0., 0., -2.));
+        assert_eq!(aabbs[1].max, Vec3::new(2., 2., -2.));
+
+        match &scene.to_objects()[0].material {
+            Material::Microfacet(m) => {
+                assert_eq!(m.albedo.average(), Vec3::new(1., 0.5, 0.5));
+                assert!(relative_eq!(m.roughness, 0.2));
+            },
+            _ => panic!("expected microfacet"),
+        }
+    }
+
+    // quad with vertex colors and an unknown element, written as a single fan
+    const PLY: &str = "ply
+format ascii 1.0
+comment made by hand
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 255 0 0
+1 0 0 255 0 0
+1 1 0 0 0 255
+0 1 0 0 0 255
+4 0 1 2 3
+0 1
+";
+
+    #[test]
+    fn test_ply_ascii() {
+        let mesh = ply::parse(PLY.as_bytes()).unwrap().into_mesh();
+        assert_eq!(mesh.triangles_count(), 2);
+        assert_eq!(mesh.colors().unwrap()[2], Vec3::z());
+
+        let ray = Ray::new(Vec3::new(0.5, 0.25, 1.), -Vec3::z());
+        let hit = mesh.hit_surface(&ray, (0., std::f32::MAX)).unwrap();
+        assert!((hit.color.unwrap() - Vec3::new(0.75, 0., 0.25)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn test_ply_colors() {
+        let header = |kind: &str| format!("ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\n\
+                                           property float z\nproperty {0} red\nproperty {0} green\nproperty {0} blue\n\
+                                           end_header\n", kind);
+
+        // 8 bit colors of scanners are srgb encoded
+        let mesh = ply::parse(format!("{}0 0 0 128 0 255\n", header("uchar")).as_bytes()).unwrap().into_mesh();
+        let color = mesh.colors().unwrap()[0];
+        assert!(relative_eq!(color.x, crate::film::srgb_to_linear(128. / 255.), epsilon = 1e-6));
+        assert!(relative_eq!(color.x, 0.2158, epsilon = 1e-4));
+        assert_eq!((color.y, color.z), (0., 1.));
+
+        let mesh = ply::parse(format!("{}0 0 0 0.5 0 1\n", header("float")).as_bytes()).unwrap().into_mesh();
+        assert_eq!(mesh.colors().unwrap()[0], Vec3::new(0.5, 0., 1.));
+    }
+
+    #[test]
+    fn test_ply_binary_big_endian() {
+        let mut data = b"ply\nformat binary_big_endian 1.0\nelement vertex 3\nproperty double x\n\
+                         property double y\nproperty double z\nelement face 1\n\
+                         property list uchar ushort vertex_index\nend_header\n".to_vec();
+        for p in &[[0., 0., 0.], [2., 0., 0.], [0., 2., 1.]] {
+            for c in p {
+                data.extend_from_slice(&f64::to_be_bytes(*c));
+            }
+        }
+        data.push(3);
+        for i in &[0u16, 1, 2] {
+            data.extend_from_slice(&i.to_be_bytes());
+        }
+
+        let triangles = ply::parse(&data).unwrap().triangles();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].v2, Vec3::new(0., 2., 1.));
+    }
+
+    #[test]
+    fn test_stl() {
+        let ascii = "solid tri
+facet normal 0 0 1
+outer loop
+vertex 0 0 0
+vertex 1 0 0
+vertex 0 1 0
+endloop
+endfacet
+endsolid tri
+";
+        let triangles = stl::parse(ascii.as_bytes()).unwrap().triangles();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].v1, Vec3::x());
+
+        // header starts with "solid" as written by some exporters
+        let mut binary = b"solid binary".to_vec();
+        binary.resize(80, 0);
+        binary.extend_from_slice(&2u32.to_le_bytes());
+        for tri in 0..2 {
+            for c in &[0., 0., 1., 0., 0., tri as f32, 1., 0., tri as f32, 0., 1., tri as f32] {
+                binary.extend_from_slice(&f32::to_le_bytes(*c));
+            }
+            binary.extend_from_slice(&[0, 0]);
+        }
+
+        let triangles = stl::parse(&binary).unwrap().triangles();
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[1].v0, Vec3::new(0., 0., 1.));
+        assert_eq!(triangles[1].v2, Vec3::new(0., 1., 1.));
+    }
+
+    #[test]
+    fn test_malformed_models() {
+        let malformed = |result: Result<MeshBuffers, ModelError>| match result {
+            Err(ModelError::Malformed { .. }) => {},
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("malformed model is loaded"),
+        };
+
+        malformed(ply::parse(b"plx\nformat ascii 1.0\nend_header\n"));
+        malformed(ply::parse(PLY.replace("4 0 1 2 3", "3 0 1 7").as_bytes()));
+        malformed(ply::parse(PLY.replace("0 1\n", "").as_bytes()));
+        malformed(ply::parse(PLY.replace("property uchar blue", "property quad blue").as_bytes()));
+        malformed(stl::parse(b"solid\nfacet\nvertex 0 0 0\nvertex 1 0\n"));
+        malformed(stl::parse(b"not a model"));
+
+        match load_geometry(Path::new("model.fbx")) {
+            Err(ModelError::UnknownFormat(_)) => {},
+            _ => panic!("expected unknown format"),
+        }
+    }
+}
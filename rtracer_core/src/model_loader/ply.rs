@@ -0,0 +1,314 @@
+use crate::{Vec3, Vec2};
+use crate::mesh::VertexAttributes;
+use crate::film::srgb_to_linear;
+
+use super::{ModelError, MeshBuffers};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // integer colors are srgb encoded in full range of the type, float colors are linear
+    fn decode_color(self, c: f64) -> f32 {
+        match self {
+            Scalar::U8 => srgb_to_linear((c / 255.) as f32),
+            Scalar::U16 => srgb_to_linear((c / 65535.) as f32),
+            _ => c as f32,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|p| p.name == name)
+    }
+
+    // first pair of names present in the element
+    fn property_pair(&self, names: &[(&str, &str)]) -> Option<(usize, usize)> {
+        names.iter().filter_map(|(a, b)| Some((self.property(a)?, self.property(b)?))).next()
+    }
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+fn malformed<S: Into<String>>(reason: S) -> ModelError {
+    ModelError::Malformed { format: "ply", reason: reason.into() }
+}
+
+// header is always ascii, body starts right after end_header line
+fn parse_header(data: &[u8]) -> Result<(Header, usize), ModelError> {
+    let mut pos = if data.starts_with(b"ply\n") {
+        4
+    } else if data.starts_with(b"ply\r\n") {
+        5
+    } else {
+        return Err(malformed("missing ply magic number"))
+    };
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+
+    loop {
+        let end = data[pos..].iter().position(|b| *b == b'\n')
+            .ok_or_else(|| malformed("header without end_header"))?;
+        let line = std::str::from_utf8(&data[pos..pos + end]).map_err(|_| malformed("header is not ascii"))?;
+        pos += end + 1;
+
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", name, _] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(malformed(format!("unknown format '{}'", name))),
+                });
+            },
+            ["comment", ..] | ["obj_info", ..] | [] => {},
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| malformed(format!("invalid element count '{}'", count)))?;
+                elements.push(Element { name: name.to_string(), count, properties: vec![] });
+            },
+            ["property", "list", count, item, name] => {
+                let kind = PropertyKind::List { count: parse_scalar(count)?, item: parse_scalar(item)? };
+                add_property(&mut elements, name, kind)?;
+            },
+            ["property", scalar, name] => {
+                let kind = PropertyKind::Scalar(parse_scalar(scalar)?);
+                add_property(&mut elements, name, kind)?;
+            },
+            ["end_header"] => break,
+            _ => return Err(malformed(format!("unexpected header line '{}'", line.trim()))),
+        }
+    }
+
+    let format = format.ok_or_else(|| malformed("header without format"))?;
+    Ok((Header { format, elements }, pos))
+}
+
+fn parse_scalar(name: &str) -> Result<Scalar, ModelError> {
+    Scalar::from_name(name).ok_or_else(|| malformed(format!("unknown property type '{}'", name)))
+}
+
+fn add_property(elements: &mut [Element], name: &str, kind: PropertyKind) -> Result<(), ModelError> {
+    let element = elements.last_mut().ok_or_else(|| malformed("property before any element"))?;
+    element.properties.push(Property { name: name.to_string(), kind });
+    Ok(())
+}
+
+enum Body<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary { data: &'a [u8], pos: usize, big_endian: bool },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, ModelError> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens.next().ok_or_else(|| malformed("unexpected end of file"))?;
+                token.parse().map_err(|_| malformed(format!("invalid number '{}'", token)))
+            },
+            Body::Binary { data, pos, big_endian } => {
+                let size = scalar.size();
+                let bytes = data.get(*pos..*pos + size).ok_or_else(|| malformed("unexpected end of file"))?;
+                *pos += size;
+
+                let mut b = [0u8; 8];
+                b[..size].copy_from_slice(bytes);
+                if *big_endian {
+                    b[..size].reverse();
+                }
+
+                Ok(match scalar {
+                    Scalar::I8 => f64::from(b[0] as i8),
+                    Scalar::U8 => f64::from(b[0]),
+                    Scalar::I16 => f64::from(i16::from_le_bytes([b[0], b[1]])),
+                    Scalar::U16 => f64::from(u16::from_le_bytes([b[0], b[1]])),
+                    Scalar::I32 => f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    Scalar::U32 => f64::from(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    Scalar::F32 => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    Scalar::F64 => f64::from_le_bytes(b),
+                })
+            },
+        }
+    }
+
+    fn read_index(&mut self, scalar: Scalar) -> Result<u32, ModelError> {
+        let value = self.read(scalar)?;
+        if value < 0. || value.fract() != 0. || value > f64::from(u32::MAX) {
+            return Err(malformed(format!("invalid index {}", value)))
+        }
+        Ok(value as u32)
+    }
+}
+
+// vertex and face elements are used, the rest is skipped
+// polygons are triangulated as fans
+pub fn parse(data: &[u8]) -> Result<MeshBuffers, ModelError> {
+    let (header, body_start) = parse_header(data)?;
+
+    let body = &data[body_start..];
+    let mut body = match header.format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(body).map_err(|_| malformed("ascii body is not utf-8"))?;
+            Body::Ascii(text.split_whitespace())
+        },
+        Format::BinaryLittleEndian => Body::Binary { data: body, pos: 0, big_endian: false },
+        Format::BinaryBigEndian => Body::Binary { data: body, pos: 0, big_endian: true },
+    };
+
+    let mut positions = vec![];
+    let mut indices = vec![];
+    let mut attributes = VertexAttributes::default();
+
+    let mut values = vec![];
+    let mut polygon = vec![];
+
+    for element in &header.elements {
+        let is_vertex = element.name == "vertex";
+        let position = match (element.property("x"), element.property("y"), element.property("z")) {
+            (Some(x), Some(y), Some(z)) if is_vertex => Some((x, y, z)),
+            _ if is_vertex => return Err(malformed("vertex element without x, y, z")),
+            _ => None,
+        };
+        let normal = (element.property("nx"), element.property("ny"), element.property("nz"));
+        let uv = element.property_pair(&[("u", "v"), ("s", "t"), ("texture_u", "texture_v"), ("texture_s", "texture_t")]);
+        let color = (element.property("red"), element.property("green"), element.property("blue"));
+
+        let face_indices = if element.name == "face" {
+            let idx = element.property("vertex_indices").or_else(|| element.property("vertex_index"))
+                .ok_or_else(|| malformed("face element without vertex_indices"))?;
+            match element.properties[idx].kind {
+                PropertyKind::List { .. } => Some(idx),
+                PropertyKind::Scalar(_) => return Err(malformed("vertex_indices is not a list")),
+            }
+        } else {
+            None
+        };
+
+        let mut normals = vec![];
+        let mut uvs = vec![];
+        let mut colors = vec![];
+
+        for _ in 0..element.count {
+            values.clear();
+            for (idx, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    PropertyKind::Scalar(scalar) => values.push(body.read(scalar)?),
+                    PropertyKind::List { count, item } => {
+                        let count = body.read_index(count)?;
+                        if Some(idx) == face_indices {
+                            polygon.clear();
+                            for _ in 0..count {
+                                polygon.push(body.read_index(item)?);
+                            }
+                        } else {
+                            for _ in 0..count {
+                                body.read(item)?;
+                            }
+                        }
+                        values.push(0.);
+                    },
+                }
+            }
+
+            let vec3 = |(x, y, z): (usize, usize, usize)| Vec3::new(values[x] as f32, values[y] as f32, values[z] as f32);
+
+            if let Some(position) = position {
+                positions.push(vec3(position));
+                if let (Some(x), Some(y), Some(z)) = normal {
+                    normals.push(vec3((x, y, z)));
+                }
+                if let Some((u, v)) = uv {
+                    uvs.push(Vec2::new(values[u] as f32, values[v] as f32));
+                }
+                if let (Some(r), Some(g), Some(b)) = color {
+                    let decode = |i: usize| match element.properties[i].kind {
+                        PropertyKind::Scalar(scalar) => scalar.decode_color(values[i]),
+                        PropertyKind::List { .. } => values[i] as f32,
+                    };
+                    colors.push(Vec3::new(decode(r), decode(g), decode(b)));
+                }
+            }
+
+            if face_indices.is_some() {
+                for i in 1..polygon.len().saturating_sub(1) {
+                    indices.push([polygon[0], polygon[i], polygon[i + 1]]);
+                }
+            }
+        }
+
+        if is_vertex {
+            attributes.normals = Some(normals).filter(|n| !n.is_empty());
+            attributes.uvs = Some(uvs).filter(|uv| !uv.is_empty());
+            attributes.colors = Some(colors).filter(|c| !c.is_empty());
+        }
+    }
+
+    if let Some(i) = indices.iter().flat_map(|tri| tri.iter()).find(|i| **i as usize >= positions.len()) {
+        return Err(malformed(format!("vertex index {} out of range", i)))
+    }
+
+    Ok(MeshBuffers { positions, indices, attributes })
+}
@@ -0,0 +1,70 @@
+use crate::Vec3;
+use crate::mesh::VertexAttributes;
+
+use super::{ModelError, MeshBuffers};
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+
+fn malformed<S: Into<String>>(reason: S) -> ModelError {
+    ModelError::Malformed { format: "stl", reason: reason.into() }
+}
+
+// stl is a triangle soup, vertices are not shared and stored normals are ignored
+pub fn parse(data: &[u8]) -> Result<MeshBuffers, ModelError> {
+    // ascii files may start with "solid" too, size of binary ones is exactly known
+    let positions = if is_binary(data) {
+        parse_binary(data)
+    } else if data.starts_with(b"solid") {
+        parse_ascii(data)?
+    } else {
+        return Err(malformed("neither ascii nor binary stl"))
+    };
+
+    let indices = (0..positions.len() as u32 / 3).map(|i| [3 * i, 3 * i + 1, 3 * i + 2]).collect();
+    Ok(MeshBuffers { positions, indices, attributes: VertexAttributes::default() })
+}
+
+fn is_binary(data: &[u8]) -> bool {
+    if data.len() < HEADER_SIZE + 4 {
+        return false
+    }
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    data.len() == HEADER_SIZE + 4 + count * TRIANGLE_SIZE
+}
+
+// normal, 3 vertices and 2 bytes of attributes per triangle
+fn parse_binary(data: &[u8]) -> Vec<Vec3> {
+    let mut positions = vec![];
+    for tri in data[HEADER_SIZE + 4..].chunks_exact(TRIANGLE_SIZE) {
+        let read_f32 = |at: usize| f32::from_le_bytes([tri[at], tri[at + 1], tri[at + 2], tri[at + 3]]);
+        for v in 1..4 {
+            positions.push(Vec3::new(read_f32(12 * v), read_f32(12 * v + 4), read_f32(12 * v + 8)));
+        }
+    }
+    positions
+}
+
+fn parse_ascii(data: &[u8]) -> Result<Vec<Vec3>, ModelError> {
+    let text = std::str::from_utf8(data).map_err(|_| malformed("ascii stl is not utf-8"))?;
+
+    let mut positions = vec![];
+    let mut tokens = text.split_whitespace();
+    while let Some(token) = tokens.next() {
+        if token != "vertex" {
+            continue
+        }
+
+        let mut coord = || -> Result<f32, ModelError> {
+            let token = tokens.next().ok_or_else(|| malformed("unexpected end of file"))?;
+            token.parse().map_err(|_| malformed(format!("invalid number '{}'", token)))
+        };
+        positions.push(Vec3::new(coord()?, coord()?, coord()?));
+    }
+
+    if positions.len() % 3 != 0 {
+        return Err(malformed("facet with vertex count other than 3"))
+    }
+
+    Ok(positions)
+}
@@ -1,6 +1,6 @@
 use crate::prelude::*;
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct Object {
     pub primitive: Primitive,
     pub material: Material,
@@ -30,6 +30,10 @@ impl Object {
     pub fn new_disk(disk: Disk, material: Material) -> Object {
         Object::new(Primitive::Disk(disk), material)
     }
+
+    pub fn new_instance(instance: Instance, material: Material) -> Object {
+        Object::new(Primitive::Instance(instance), material)
+    }
 }
 
 impl Bounded for Object {
@@ -30,8 +30,9 @@ impl Intersect for Plane {
     }
 }
 
+// planes are infinite, bvh builders keep them out of the tree
 impl Bounded for Plane {
     fn aabb(&self) -> Aabb {
-        unimplemented!()
+        Aabb::infinite()
     }
 }
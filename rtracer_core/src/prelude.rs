@@ -1,5 +1,6 @@
 pub use crate::{
     Vec3,
+    Vec2,
     ray::Ray,
     aabb::Aabb,
     sphere::Sphere,
@@ -7,12 +8,25 @@ pub use crate::{
     cube::Cube,
     triangle::Triangle,
     disk::Disk,
+    cylinder::Cylinder,
+    cone::Cone,
+    capsule::Capsule,
+    torus::Torus,
+    sdf::{Sdf, SdfNode},
+    csg::{Csg, CsgOp, Solid, Span, Crossing},
+    transform::Transform,
+    instance::Instance,
+    mesh::{Mesh, VertexAttributes},
     primitive::Primitive,
-    intersect::Intersect,
+    intersect::{Intersect, SurfaceHit},
     object::Object,
-    camera::{Camera, RaycastCamera},
-    material::{Material, Lambertian, Metal, Dielectric},
+    camera::{Camera, RaycastCamera, Projection},
+    material::{Material, Lambertian, Metal, Dielectric, Emissive, Microfacet, Fresnel},
+    texture::{Texture, Checker, Noise, Perlin, ImageTexture, WrapMode, Filter},
+    environment::{Background, EnvironmentMap},
+    sky::Sky,
     scene_data::*,
+    scene_file::{SceneFile, SceneFileError, RenderSettings},
     intersection,
     bounded::Bounded,
 };
@@ -1,12 +1,45 @@
 use crate::prelude::*;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum Primitive {
     Sphere(Sphere),
     Plane(Plane),
     Cube(Cube),
     Triangle(Triangle),
     Disk(Disk),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    Capsule(Capsule),
+    Torus(Torus),
+    Sdf(Sdf),
+    Csg(Csg),
+    Instance(Instance),
+    Mesh(Mesh),
+}
+
+impl Primitive {
+    pub fn hit_surface(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<SurfaceHit> {
+        let point = |t| ray.point_at_parameter(t);
+        match self {
+            Primitive::Sphere(s) => s.intersect(ray, t_min_max).map(|t| {
+                let (s, point) = (s.at_time(ray.time), point(t));
+                SurfaceHit::with_uv(t, s.normal_at(&point), s.uv_at(&point))
+            }),
+            Primitive::Plane(s) => s.intersect(ray, t_min_max).map(|t| SurfaceHit::new(t, s.normal)),
+            Primitive::Cube(s) => s.intersect(ray, t_min_max).map(|t| SurfaceHit::new(t, s.normal_at(&point(t)))),
+            Primitive::Triangle(s) => s.intersect_barycentric(ray, t_min_max)
+                .map(|(t, u, v)| SurfaceHit::with_uv(t, s.normal(), Vec2::new(u, v))),
+            Primitive::Disk(s) => s.intersect(ray, t_min_max).map(|t| SurfaceHit::new(t, s.plane.normal)),
+            Primitive::Cylinder(s) => s.intersect(ray, t_min_max).map(|t| SurfaceHit::new(t, s.normal_at(&point(t)))),
+            Primitive::Cone(s) => s.intersect(ray, t_min_max).map(|t| SurfaceHit::new(t, s.normal_at(&point(t)))),
+            Primitive::Capsule(s) => s.intersect(ray, t_min_max).map(|t| SurfaceHit::new(t, s.normal_at(&point(t)))),
+            Primitive::Torus(s) => s.intersect(ray, t_min_max).map(|t| SurfaceHit::new(t, s.normal_at(&point(t)))),
+            Primitive::Sdf(s) => s.intersect(ray, t_min_max).map(|t| SurfaceHit::new(t, s.normal_at(&point(t)))),
+            Primitive::Csg(s) => s.hit_surface(ray, t_min_max),
+            Primitive::Instance(s) => s.hit_surface(ray, t_min_max),
+            Primitive::Mesh(s) => s.hit_surface(ray, t_min_max),
+        }
+    }
 }
 
 impl Intersect for Primitive {
@@ -17,6 +50,14 @@ impl Intersect for Primitive {
             Primitive::Cube(s) => s.intersect(ray, t_min_max),
             Primitive::Triangle(s) => s.intersect(ray, t_min_max),
             Primitive::Disk(s) => s.intersect(ray, t_min_max),
+            Primitive::Cylinder(s) => s.intersect(ray, t_min_max),
+            Primitive::Cone(s) => s.intersect(ray, t_min_max),
+            Primitive::Capsule(s) => s.intersect(ray, t_min_max),
+            Primitive::Torus(s) => s.intersect(ray, t_min_max),
+            Primitive::Sdf(s) => s.intersect(ray, t_min_max),
+            Primitive::Csg(s) => s.intersect(ray, t_min_max),
+            Primitive::Instance(s) => s.intersect(ray, t_min_max),
+            Primitive::Mesh(s) => s.intersect(ray, t_min_max),
         }
     }
 }
@@ -29,6 +70,14 @@ impl Bounded for Primitive {
             Primitive::Cube(s) => s.aabb(),
             Primitive::Triangle(s) => s.aabb(),
             Primitive::Disk(s) => s.aabb(),
+            Primitive::Cylinder(s) => s.aabb(),
+            Primitive::Cone(s) => s.aabb(),
+            Primitive::Capsule(s) => s.aabb(),
+            Primitive::Torus(s) => s.aabb(),
+            Primitive::Sdf(s) => s.aabb(),
+            Primitive::Csg(s) => s.aabb(),
+            Primitive::Instance(s) => s.aabb(),
+            Primitive::Mesh(s) => s.aabb(),
         }
     }
 }
@@ -50,3 +99,51 @@ impl From<Triangle> for Primitive {
         Primitive::Triangle(t)
     }
 }
+
+impl From<Cylinder> for Primitive {
+    fn from(c: Cylinder) -> Self {
+        Primitive::Cylinder(c)
+    }
+}
+
+impl From<Cone> for Primitive {
+    fn from(c: Cone) -> Self {
+        Primitive::Cone(c)
+    }
+}
+
+impl From<Capsule> for Primitive {
+    fn from(c: Capsule) -> Self {
+        Primitive::Capsule(c)
+    }
+}
+
+impl From<Torus> for Primitive {
+    fn from(t: Torus) -> Self {
+        Primitive::Torus(t)
+    }
+}
+
+impl From<Sdf> for Primitive {
+    fn from(s: Sdf) -> Self {
+        Primitive::Sdf(s)
+    }
+}
+
+impl From<Csg> for Primitive {
+    fn from(c: Csg) -> Self {
+        Primitive::Csg(c)
+    }
+}
+
+impl From<Instance> for Primitive {
+    fn from(i: Instance) -> Self {
+        Primitive::Instance(i)
+    }
+}
+
+impl From<Mesh> for Primitive {
+    fn from(m: Mesh) -> Self {
+        Primitive::Mesh(m)
+    }
+}
@@ -4,11 +4,17 @@ use crate::Vec3;
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    // moment inside of shutter interval, moving objects are at their start at 0 and at end at 1
+    pub time: f32,
 }
 
 impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Ray {
-        Ray { origin, direction: direction.normalize() }
+        Ray::with_time(origin, direction, 0.)
+    }
+
+    pub fn with_time(origin: Vec3, direction: Vec3, time: f32) -> Ray {
+        Ray { origin, direction: direction.normalize(), time }
     }
 
     pub fn point_at_parameter(&self, t: f32) -> Vec3 {
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use crate::primitive::Primitive;
 use crate::material::Material;
+use crate::object::Object;
 
 // !todo: remove pub from inner u32
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -124,6 +125,19 @@ impl SceneData {
 
         self.add_object(primitive, material).unwrap()
     }
+
+    // resolves ids, so scene can be used with renderers working on objects
+    pub fn to_objects(&self) -> Vec<Object> {
+        let mut ids: Vec<_> = self.objects.keys().collect();
+        ids.sort_by_key(|id| id.0);
+
+        ids.into_iter()
+            .map(|id| {
+                let object = &self.objects[id];
+                Object::new(self.primitives[&object.primitive].clone(), self.materials[&object.material].clone())
+            })
+            .collect()
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -0,0 +1,832 @@
+//! JSON scene description.
+//!
+//! ```json
+//! {
+//!     "settings": { "width": 400, "height": 400, "samples": 64, "max_depth": 16, "seed": 0,
+//!                   "background": [0, 0, 0], "exposure": 0, "tone_mapping": "aces" },
+//!     "environment": { "type": "map", "path": "studio.hdr", "rotation": 90, "intensity": 1.5 },
+//!     "camera": { "lookfrom": [0, 0, 3.4], "lookat": [0, 0, 0], "vup": [0, 1, 0], "vfov": 40 },
+//!     "materials": {
+//!         "white": { "type": "lambertian", "albedo": [0.73, 0.73, 0.73] },
+//!         "gold": { "type": "metal", "albedo": [0.8, 0.6, 0.2], "fuzz": 0.1 },
+//!         "tiles": { "type": "lambertian", "albedo": { "type": "checker", "even": [1, 1, 1], "odd": [0, 0, 0], "scale": 8 } },
+//!         "marble": { "type": "lambertian", "albedo": { "type": "noise", "color": [0.9, 0.9, 0.8], "scale": 4, "octaves": 6 } },
+//!         "wood": { "type": "lambertian", "albedo": { "type": "image", "path": "wood.png", "wrap": "mirror", "filter": "bilinear" } },
+//!         "copper": { "type": "microfacet", "albedo": [0.95, 0.64, 0.54], "roughness": 0.3 },
+//!         "plastic": { "type": "microfacet", "albedo": [0.1, 0.2, 0.8], "roughness": 0.2,
+//!                      "fresnel": { "type": "dielectric", "ior": 1.5 } },
+//!         "glass": { "type": "dielectric", "attenuation": [1, 1, 1], "ref_idx": 1.5 },
+//!         "lamp": { "type": "emissive", "radiance": [15, 15, 15] }
+//!     },
+//!     "objects": [
+//!         { "type": "sphere", "center": [0, 0, 0], "radius": 0.5, "material": "gold" },
+//!         { "type": "plane", "origin": [0, -1, 0], "normal": [0, 1, 0], "material": "white" },
+//!         { "type": "cube", "center": [0, 0, 0], "size": [1, 1, 1], "material": "white" },
+//!         { "type": "triangle", "v0": [0, 0, 0], "v1": [1, 0, 0], "v2": [0, 1, 0], "material": "white" },
+//!         { "type": "disk", "origin": [0, 1, 0], "normal": [0, -1, 0], "radius": 0.3, "material": "lamp" },
+//!         { "type": "cylinder", "base": [0, 0, 0], "top": [0, 1, 0], "radius": 0.2, "material": "white" },
+//!         { "type": "cone", "base": [0, 0, 0], "apex": [0, 1, 0], "radius": 0.5, "material": "white" },
+//!         { "type": "capsule", "a": [0, 0, 0], "b": [1, 0, 0], "radius": 0.1, "material": "white" },
+//!         { "type": "torus", "center": [0, 0, 0], "axis": [0, 1, 0], "major_radius": 1, "minor_radius": 0.2,
+//!           "material": "gold" },
+//!         { "type": "sdf", "material": "white", "shape": { "type": "smooth_union", "k": 0.3,
+//!           "a": { "type": "sphere", "radius": 0.5 },
+//!           "b": { "type": "translate", "offset": [0.6, 0, 0], "node": { "type": "box", "half_size": [0.3, 0.3, 0.3] } } } },
+//!         { "type": "csg", "op": "difference", "material": "glass",
+//!           "a": { "type": "sphere", "center": [0, 0, 0], "radius": 1 },
+//!           "b": { "type": "cube", "center": [1, 1, 1], "size": [2, 2, 2] } },
+//!         { "type": "obj", "path": "../models/cube.obj", "material": "glass",
+//!           "transform": { "scale": [1, 2, 1], "rotate": [0, 45, 0], "translate": [0, 1, 0] } },
+//!         { "type": "mesh", "path": "../models/bunny.ply", "material": "marble" }
+//!     ]
+//! }
+//! ```
+//!
+//! * all vectors and colors are `[x, y, z]` arrays
+//! * every field of `settings` is optional, see `RenderSettings` for defaults
+//! * optional `environment` replaces `settings.background`, `map` is an equirectangular image lighting
+//!   the scene, `rotation` around y in degrees and `intensity` multiplier are optional
+//! * `{ "type": "sky", "sun_direction": [1, 0.5, -1], "turbidity": 3 }` environment is a daylight sky with sun,
+//!   `turbidity` goes from 1.7 (clear) to 10 (hazy), `intensity` is optional too
+//! * `camera.aspect` is optional and defaults to `width / height`
+//! * optional `camera.aperture` is lens diameter for depth of field (0 by default),
+//!   `camera.focus_distance` defaults to distance from `lookfrom` to `lookat`
+//! * optional `camera.projection` is `{ "type": "perspective" }` (default), `{ "type": "orthographic", "height": 4 }`
+//!   with view height in world units, `{ "type": "fisheye", "fov": 180 }` with fov across image height
+//!   or `{ "type": "equirectangular" }`, lens is used by perspective projection only
+//! * optional `camera.shutter` is `[open, close]` time within [0, 1] for motion blur (`[0, 0]` by default),
+//!   `camera.motion` is translation of the camera at time 1
+//! * `albedo` is a color or a texture: uv `checker`, solid `noise` (fbm of perlin noise) or `image`
+//!   (png, jpeg, hdr; `wrap` is `repeat`, `mirror` or `clamp`, `filter` is `nearest` or `bilinear`)
+//! * `microfacet` is a ggx surface, `fresnel` is `schlick` (default, albedo is reflectance of metal),
+//!   `conductor` with complex ior `eta`, `k` or `dielectric` coat with `ior` over diffuse albedo
+//! * objects refer to materials by name
+//! * cylinders and cones have caps unless `"capped": false`, torus `axis` is `[0, 1, 0]` by default
+//! * `sdf` shape is a tree of distance functions centered at origin: `sphere`, `box`, `rounded_box`,
+//!   `torus` (in xz plane), `translate`, `union`, `smooth_union`, `subtraction` (`a` without `b`)
+//!   and `repeat` with `count` copies along each axis at `spacing`, use `transform` to place it
+//! * `csg` is `union`, `intersection` or `difference` (`a` without `b`) of closed primitives or other csg,
+//!   children are objects without material and may have own `transform`, allowed children are
+//!   `sphere`, `cube`, capped `cylinder` and `cone`, `capsule`, `torus` and `csg`
+//! * `obj` models without `material` use materials from their mtl files
+//! * `gltf` models (`.gltf` or `.glb`) always use their own materials, cameras of them are ignored
+//! * `SceneFile::load` also accepts a `.gltf`/`.glb` file directly, it must contain a camera
+//! * `mesh` loads `.obj`, `.ply` or `.stl` geometry chosen by extension, `material` is required
+//! * model and texture paths are relative to the scene file
+//! * optional object `transform` is applied as scale, rotation around x, y, z (degrees), translation
+//! * optional object `motion` is its translation at time 1, objects move linearly during the shutter,
+//!   `gltf` models ignore it
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Serialize, Deserialize};
+
+use crate::Vec3;
+use crate::camera::{Camera, Projection};
+use crate::film::{Film, ToneMapping};
+use crate::material::{Material, Lambertian, Metal, Dielectric, Emissive, Microfacet, Fresnel};
+use crate::texture::{Texture, Checker, Noise, ImageTexture, WrapMode, Filter};
+use crate::image::ImageError;
+use crate::environment::{Background, EnvironmentMap};
+use crate::sky::Sky;
+use crate::primitive::Primitive;
+use crate::sphere::Sphere;
+use crate::plane::Plane;
+use crate::cube::Cube;
+use crate::triangle::Triangle;
+use crate::disk::Disk;
+use crate::cylinder::Cylinder;
+use crate::cone::Cone;
+use crate::capsule::Capsule;
+use crate::torus::Torus;
+use crate::sdf::{Sdf, SdfNode};
+use crate::csg::{Csg, CsgOp};
+use crate::transform::Transform;
+use crate::instance::Instance;
+use crate::scene_data::{SceneData, MaterialId};
+use crate::model_loader::{ModelError, load_obj_with_materials, load_gltf, load_mesh};
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnknownMaterial(String),
+    MissingMaterial,
+    MissingCamera,
+    EmptyScene,
+    // only closed primitives can be children of csg
+    NotSolid,
+    Model(PathBuf, ModelError),
+    Texture(PathBuf, ImageError),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Io(err) => write!(f, "cant read scene file: {}", err),
+            SceneFileError::Json(err) => write!(f, "invalid scene file: {}", err),
+            SceneFileError::UnknownMaterial(name) => write!(f, "unknown material '{}'", name),
+            SceneFileError::MissingMaterial => write!(f, "object without material"),
+            SceneFileError::MissingCamera => write!(f, "scene without camera"),
+            SceneFileError::EmptyScene => write!(f, "scene without objects"),
+            SceneFileError::NotSolid => write!(f, "csg child is not a closed primitive"),
+            SceneFileError::Model(path, err) => write!(f, "cant load model {}: {}", path.display(), err),
+            SceneFileError::Texture(path, err) => write!(f, "cant load texture {}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+impl From<std::io::Error> for SceneFileError {
+    fn from(err: std::io::Error) -> Self {
+        SceneFileError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SceneFileError {
+    fn from(err: serde_json::Error) -> Self {
+        SceneFileError::Json(err)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32,
+    pub max_depth: u32,
+    pub seed: u64,
+    pub background: [f32; 3],
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings { width: 200, height: 100, samples: 32, max_depth: 64, seed: 0, background: [1., 1., 1.],
+                         exposure: 0., tone_mapping: ToneMapping::Clamp }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraDesc {
+    pub lookfrom: [f32; 3],
+    pub lookat: [f32; 3],
+    #[serde(default = "default_vup")]
+    pub vup: [f32; 3],
+    pub vfov: f32,
+    #[serde(default)]
+    pub aspect: Option<f32>,
+    #[serde(default)]
+    pub aperture: f32,
+    #[serde(default)]
+    pub focus_distance: Option<f32>,
+    #[serde(default)]
+    pub projection: Projection,
+    #[serde(default)]
+    pub shutter: [f32; 2],
+    #[serde(default)]
+    pub motion: [f32; 3],
+}
+
+fn default_vup() -> [f32; 3] {
+    [0., 1., 0.]
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaterialDesc {
+    Lambertian { albedo: TextureDesc },
+    Metal { albedo: TextureDesc, #[serde(default)] fuzz: f32 },
+    Dielectric { #[serde(default = "default_attenuation")] attenuation: [f32; 3], ref_idx: f32 },
+    Emissive { radiance: [f32; 3] },
+    Microfacet { albedo: TextureDesc, roughness: f32, #[serde(default)] fresnel: FresnelDesc },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FresnelDesc {
+    Schlick,
+    Conductor { eta: [f32; 3], k: [f32; 3] },
+    Dielectric { #[serde(default = "default_ior")] ior: f32 },
+}
+
+impl Default for FresnelDesc {
+    fn default() -> FresnelDesc {
+        FresnelDesc::Schlick
+    }
+}
+
+fn default_ior() -> f32 {
+    1.5
+}
+
+fn default_attenuation() -> [f32; 3] {
+    [1., 1., 1.]
+}
+
+// plain color or texture object
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TextureDesc {
+    Color([f32; 3]),
+    Texture(TextureKindDesc),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextureKindDesc {
+    Checker { even: [f32; 3], odd: [f32; 3], #[serde(default = "default_texture_scale")] scale: f32 },
+    Noise {
+        color: [f32; 3],
+        #[serde(default = "default_texture_scale")] scale: f32,
+        #[serde(default = "default_octaves")] octaves: u32,
+        #[serde(default)] seed: u64,
+    },
+    Image { path: PathBuf, #[serde(default)] wrap: WrapMode, #[serde(default)] filter: Filter },
+}
+
+fn default_texture_scale() -> f32 {
+    1.
+}
+
+fn default_octaves() -> u32 {
+    4
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EnvironmentDesc {
+    Map { path: PathBuf, #[serde(default)] rotation: f32, #[serde(default = "default_intensity")] intensity: f32 },
+    Sky {
+        sun_direction: [f32; 3],
+        #[serde(default = "default_turbidity")] turbidity: f32,
+        #[serde(default = "default_intensity")] intensity: f32,
+    },
+}
+
+fn default_capped() -> bool {
+    true
+}
+
+fn default_turbidity() -> f32 {
+    3.
+}
+
+fn default_intensity() -> f32 {
+    1.
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PrimitiveDesc {
+    Sphere { center: [f32; 3], radius: f32 },
+    Plane { origin: [f32; 3], normal: [f32; 3] },
+    Cube { center: [f32; 3], size: [f32; 3] },
+    Triangle { v0: [f32; 3], v1: [f32; 3], v2: [f32; 3] },
+    Disk { origin: [f32; 3], normal: [f32; 3], radius: f32 },
+    Cylinder { base: [f32; 3], top: [f32; 3], radius: f32, #[serde(default = "default_capped")] capped: bool },
+    Cone { base: [f32; 3], apex: [f32; 3], radius: f32, #[serde(default = "default_capped")] capped: bool },
+    Capsule { a: [f32; 3], b: [f32; 3], radius: f32 },
+    Torus { center: [f32; 3], #[serde(default = "default_vup")] axis: [f32; 3], major_radius: f32, minor_radius: f32 },
+    Sdf { shape: SdfDesc },
+    Csg { op: CsgOp, a: Box<CsgChildDesc>, b: Box<CsgChildDesc> },
+    Obj { path: PathBuf },
+    Gltf { path: PathBuf },
+    // any format known to model loader, materials of the file are ignored
+    Mesh { path: PathBuf },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SdfDesc {
+    Sphere { radius: f32 },
+    Box { half_size: [f32; 3] },
+    RoundedBox { half_size: [f32; 3], radius: f32 },
+    Torus { major_radius: f32, minor_radius: f32 },
+    Translate { offset: [f32; 3], node: Box<SdfDesc> },
+    Union { a: Box<SdfDesc>, b: Box<SdfDesc> },
+    SmoothUnion { a: Box<SdfDesc>, b: Box<SdfDesc>, k: f32 },
+    Subtraction { a: Box<SdfDesc>, b: Box<SdfDesc> },
+    Repeat { spacing: [f32; 3], count: [u32; 3], node: Box<SdfDesc> },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CsgChildDesc {
+    #[serde(flatten)]
+    pub primitive: PrimitiveDesc,
+    #[serde(default)]
+    pub transform: Option<TransformDesc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ObjectDesc {
+    #[serde(flatten)]
+    pub primitive: PrimitiveDesc,
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default)]
+    pub transform: Option<TransformDesc>,
+    #[serde(default)]
+    pub motion: Option<[f32; 3]>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransformDesc {
+    #[serde(default = "default_scale")]
+    pub scale: [f32; 3],
+    #[serde(default)]
+    pub rotate: [f32; 3],
+    #[serde(default)]
+    pub translate: [f32; 3],
+}
+
+fn default_scale() -> [f32; 3] {
+    [1., 1., 1.]
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneDesc {
+    #[serde(default)]
+    pub settings: RenderSettings,
+    #[serde(default)]
+    pub environment: Option<EnvironmentDesc>,
+    pub camera: CameraDesc,
+    pub materials: HashMap<String, MaterialDesc>,
+    pub objects: Vec<ObjectDesc>,
+}
+
+pub struct SceneFile {
+    pub scene: SceneData,
+    pub camera: Camera,
+    pub settings: RenderSettings,
+    pub background: Background,
+}
+
+impl SceneFile {
+    pub fn load(path: &Path) -> Result<SceneFile, SceneFileError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gltf") | Some("glb") => return SceneFile::load_gltf(path),
+            _ => (),
+        }
+
+        let json = std::fs::read_to_string(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        SceneFile::from_json(&json, base_dir)
+    }
+
+    // default render settings with camera from the file
+    pub fn load_gltf(path: &Path) -> Result<SceneFile, SceneFileError> {
+        let settings = RenderSettings::default();
+        let mut scene = SceneData::new();
+
+        let camera = load_gltf(&mut scene, path, &Transform::identity(), settings.width as f32 / settings.height as f32)
+            .map_err(|err| SceneFileError::Model(path.to_path_buf(), err))?
+            .ok_or(SceneFileError::MissingCamera)?;
+
+        let background = settings.background().into();
+        Ok(SceneFile { scene, camera, settings, background })
+    }
+
+    pub fn from_json(json: &str, base_dir: &Path) -> Result<SceneFile, SceneFileError> {
+        let desc: SceneDesc = serde_json::from_str(json)?;
+        SceneFile::from_desc(&desc, base_dir)
+    }
+
+    pub fn from_desc(desc: &SceneDesc, base_dir: &Path) -> Result<SceneFile, SceneFileError> {
+        let mut scene = SceneData::new();
+
+        let mut materials = HashMap::new();
+        for (name, material) in &desc.materials {
+            materials.insert(name.as_str(), scene.add_material(material.to_material(base_dir)?));
+        }
+
+        for object in &desc.objects {
+            let material = match &object.material {
+                Some(name) => Some(*materials.get(name.as_str())
+                    .ok_or_else(|| SceneFileError::UnknownMaterial(name.clone()))?),
+                None => None,
+            };
+
+            let transform = object.transform.as_ref().map(TransformDesc::to_transform);
+            let motion = object.motion.as_ref().map(vec3);
+            add_primitive(&mut scene, &object.primitive, material, transform, motion, base_dir)?;
+        }
+
+        let settings = desc.settings;
+        let camera = desc.camera.to_camera(settings.width as f32 / settings.height as f32);
+
+        let background = match &desc.environment {
+            Some(environment) => environment.to_background(base_dir)?,
+            None => settings.background().into(),
+        };
+
+        Ok(SceneFile { scene, camera, settings, background })
+    }
+}
+
+// material is None only for obj models with mtl materials
+fn add_primitive(scene: &mut SceneData, primitive: &PrimitiveDesc, material: Option<MaterialId>, transform: Option<Transform>,
+                 motion: Option<Vec3>, base_dir: &Path) -> Result<(), SceneFileError> {
+    let add = |scene: &mut SceneData, primitive: Primitive, material: MaterialId| {
+        let primitive = match (transform, motion) {
+            (transform, Some(motion)) => Instance::moving(Arc::new(primitive), transform.unwrap_or_default(), motion).into(),
+            (Some(transform), None) => Instance::new(Arc::new(primitive), transform).into(),
+            (None, None) => primitive,
+        };
+        let primitive = scene.add_primitive(primitive);
+        scene.add_object(primitive, material);
+    };
+
+    let primitive = match primitive {
+        PrimitiveDesc::Obj { path } => {
+            let path = base_dir.join(path);
+            let (models, materials) = load_obj_with_materials(&path)
+                .map_err(|err| SceneFileError::Model(path.clone(), err))?;
+
+            // object material overrides the ones from mtl
+            let materials: Vec<_> = match material {
+                Some(_) => vec![],
+                None => materials.into_iter().map(|m| scene.add_material(m)).collect(),
+            };
+
+            for model in models {
+                let material = material
+                    .or_else(|| model.material.and_then(|idx| materials.get(idx).cloned()))
+                    .ok_or(SceneFileError::MissingMaterial)?;
+                add(scene, model.mesh.into(), material);
+            }
+            return Ok(())
+        },
+        PrimitiveDesc::Mesh { path } => {
+            let path = base_dir.join(path);
+            let meshes = load_mesh(&path).map_err(|err| SceneFileError::Model(path.clone(), err))?;
+
+            let material = material.ok_or(SceneFileError::MissingMaterial)?;
+            for mesh in meshes {
+                add(scene, mesh.into(), material);
+            }
+            return Ok(())
+        },
+        PrimitiveDesc::Gltf { path } => {
+            let path = base_dir.join(path);
+            load_gltf(scene, &path, &transform.unwrap_or_default(), 1.)
+                .map_err(|err| SceneFileError::Model(path.clone(), err))?;
+            return Ok(())
+        },
+        primitive => primitive.to_primitive()?,
+    };
+
+    add(scene, primitive, material.ok_or(SceneFileError::MissingMaterial)?);
+    Ok(())
+}
+
+impl PrimitiveDesc {
+    // single primitive, models are loaded by add_primitive
+    pub fn to_primitive(&self) -> Result<Primitive, SceneFileError> {
+        Ok(match self {
+            PrimitiveDesc::Sphere { center, radius } => Sphere::new(vec3(center), *radius).into(),
+            PrimitiveDesc::Plane { origin, normal } => Primitive::Plane(Plane::new(vec3(origin), vec3(normal).normalize())),
+            PrimitiveDesc::Cube { center, size } => Cube::new(vec3(center), vec3(size)).into(),
+            PrimitiveDesc::Triangle { v0, v1, v2 } => Triangle::new(vec3(v0), vec3(v1), vec3(v2)).into(),
+            PrimitiveDesc::Disk { origin, normal, radius } => {
+                Primitive::Disk(Disk::new(Plane::new(vec3(origin), vec3(normal).normalize()), *radius))
+            },
+            PrimitiveDesc::Cylinder { base, top, radius, capped } => {
+                Cylinder { base: vec3(base), top: vec3(top), radius: *radius, capped: *capped }.into()
+            },
+            PrimitiveDesc::Cone { base, apex, radius, capped } => {
+                Cone { base: vec3(base), apex: vec3(apex), radius: *radius, capped: *capped }.into()
+            },
+            PrimitiveDesc::Capsule { a, b, radius } => Capsule::new(vec3(a), vec3(b), *radius).into(),
+            PrimitiveDesc::Torus { center, axis, major_radius, minor_radius } => {
+                Torus::new(vec3(center), vec3(axis), *major_radius, *minor_radius).into()
+            },
+            PrimitiveDesc::Sdf { shape } => Sdf::new(shape.to_node()).into(),
+            PrimitiveDesc::Csg { op, a, b } => Csg::new(*op, Arc::new(a.to_primitive()?), Arc::new(b.to_primitive()?)).into(),
+            PrimitiveDesc::Obj { .. } | PrimitiveDesc::Gltf { .. } | PrimitiveDesc::Mesh { .. } => {
+                return Err(SceneFileError::NotSolid)
+            },
+        })
+    }
+}
+
+impl CsgChildDesc {
+    pub fn to_primitive(&self) -> Result<Primitive, SceneFileError> {
+        // others have no inside and would be empty space
+        match self.primitive {
+            PrimitiveDesc::Sphere { .. } | PrimitiveDesc::Cube { .. } | PrimitiveDesc::Capsule { .. }
+            | PrimitiveDesc::Torus { .. } | PrimitiveDesc::Csg { .. } => {},
+            PrimitiveDesc::Cylinder { capped, .. } | PrimitiveDesc::Cone { capped, .. } if capped => {},
+            _ => return Err(SceneFileError::NotSolid),
+        }
+
+        let primitive = self.primitive.to_primitive()?;
+        Ok(match &self.transform {
+            Some(transform) => Instance::new(Arc::new(primitive), transform.to_transform()).into(),
+            None => primitive,
+        })
+    }
+}
+
+impl MaterialDesc {
+    // image textures are loaded relative to base_dir
+    pub fn to_material(&self, base_dir: &Path) -> Result<Material, SceneFileError> {
+        Ok(match self {
+            MaterialDesc::Lambertian { albedo } => Lambertian::new(albedo.to_texture(base_dir)?).into(),
+            MaterialDesc::Metal { albedo, fuzz } => Metal::new(albedo.to_texture(base_dir)?, *fuzz).into(),
+            MaterialDesc::Dielectric { attenuation, ref_idx } => Dielectric::new(vec3(attenuation), *ref_idx).into(),
+            MaterialDesc::Emissive { radiance } => Emissive::new(vec3(radiance)).into(),
+            MaterialDesc::Microfacet { albedo, roughness, fresnel } => {
+                Microfacet::new(albedo.to_texture(base_dir)?, *roughness, fresnel.to_fresnel()).into()
+            },
+        })
+    }
+}
+
+impl FresnelDesc {
+    pub fn to_fresnel(&self) -> Fresnel {
+        match self {
+            FresnelDesc::Schlick => Fresnel::Schlick,
+            FresnelDesc::Conductor { eta, k } => Fresnel::Conductor { eta: vec3(eta), k: vec3(k) },
+            FresnelDesc::Dielectric { ior } => Fresnel::Dielectric { ior: *ior },
+        }
+    }
+}
+
+impl TextureDesc {
+    pub fn to_texture(&self, base_dir: &Path) -> Result<Texture, SceneFileError> {
+        let texture = match self {
+            TextureDesc::Color(color) => return Ok(vec3(color).into()),
+            TextureDesc::Texture(texture) => texture,
+        };
+
+        Ok(match texture {
+            TextureKindDesc::Checker { even, odd, scale } => Checker::new(vec3(even), vec3(odd), *scale).into(),
+            TextureKindDesc::Noise { color, scale, octaves, seed } => {
+                Noise::with_seed(vec3(color), *scale, *octaves, *seed).into()
+            },
+            TextureKindDesc::Image { path, wrap, filter } => {
+                let path = base_dir.join(path);
+                let mut image = ImageTexture::load(&path).map_err(|err| SceneFileError::Texture(path, err))?;
+                image.set_wrap(*wrap);
+                image.set_filter(*filter);
+                image.into()
+            },
+        })
+    }
+}
+
+impl EnvironmentDesc {
+    pub fn to_background(&self, base_dir: &Path) -> Result<Background, SceneFileError> {
+        match self {
+            EnvironmentDesc::Map { path, rotation, intensity } => {
+                let path = base_dir.join(path);
+                let map = EnvironmentMap::load(&path, *rotation, *intensity)
+                    .map_err(|err| SceneFileError::Texture(path, err))?;
+                Ok(map.into())
+            },
+            EnvironmentDesc::Sky { sun_direction, turbidity, intensity } => {
+                Ok(Sky::new(vec3(sun_direction), *turbidity, *intensity).into())
+            },
+        }
+    }
+}
+
+impl TransformDesc {
+    pub fn to_transform(&self) -> Transform {
+        let [x, y, z] = self.rotate;
+        Transform::scale(vec3(&self.scale))
+            .then(&Transform::rotation(Vec3::x(), x))
+            .then(&Transform::rotation(Vec3::y(), y))
+            .then(&Transform::rotation(Vec3::z(), z))
+            .then(&Transform::translation(vec3(&self.translate)))
+    }
+}
+
+impl SdfDesc {
+    pub fn to_node(&self) -> SdfNode {
+        let node = |desc: &SdfDesc| Box::new(desc.to_node());
+        match self {
+            SdfDesc::Sphere { radius } => SdfNode::Sphere { radius: *radius },
+            SdfDesc::Box { half_size } => SdfNode::Box { half_size: vec3(half_size) },
+            SdfDesc::RoundedBox { half_size, radius } => SdfNode::RoundedBox { half_size: vec3(half_size), radius: *radius },
+            SdfDesc::Torus { major_radius, minor_radius } => {
+                SdfNode::Torus { major_radius: *major_radius, minor_radius: *minor_radius }
+            },
+            SdfDesc::Translate { offset, node: n } => SdfNode::Translate { offset: vec3(offset), node: node(n) },
+            SdfDesc::Union { a, b } => SdfNode::Union { a: node(a), b: node(b) },
+            SdfDesc::SmoothUnion { a, b, k } => SdfNode::SmoothUnion { a: node(a), b: node(b), k: *k },
+            SdfDesc::Subtraction { a, b } => SdfNode::Subtraction { a: node(a), b: node(b) },
+            SdfDesc::Repeat { spacing, count, node: n } => SdfNode::Repeat { spacing: vec3(spacing), count: *count, node: node(n) },
+        }
+    }
+}
+
+impl CameraDesc {
+    pub fn to_camera(&self, default_aspect: f32) -> Camera {
+        let mut camera = Camera::new(vec3(&self.lookfrom), vec3(&self.lookat), vec3(&self.vup), self.vfov,
+                                     self.aspect.unwrap_or(default_aspect));
+        camera.projection = self.projection;
+        camera.shutter = (self.shutter[0], self.shutter[1]);
+        camera.motion = vec3(&self.motion);
+        let focus_distance = self.focus_distance.unwrap_or(camera.focus_distance);
+        camera.with_lens(self.aperture, focus_distance)
+    }
+}
+
+impl RenderSettings {
+    pub fn background(&self) -> Vec3 {
+        vec3(&self.background)
+    }
+
+    pub fn film(&self) -> Film {
+        let mut film = Film::new();
+        film.set_exposure(self.exposure);
+        film.set_tone_mapping(self.tone_mapping);
+        film
+    }
+}
+
+fn vec3(v: &[f32; 3]) -> Vec3 {
+    Vec3::from(*v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounded::Bounded;
+
+    const SCENE: &str = r#"{
+        "settings": { "width": 64, "samples": 4 },
+        "camera": { "lookfrom": [0, 0, 3], "lookat": [0, 0, 0], "vfov": 40 },
+        "materials": {
+            "white": { "type": "lambertian", "albedo": [0.73, 0.73, 0.73] },
+            "lamp": { "type": "emissive", "radiance": [15, 15, 15] }
+        },
+        "objects": [
+            { "type": "sphere", "center": [0, 0, 0], "radius": 0.5, "material": "white" },
+            { "type": "disk", "origin": [0, 1, 0], "normal": [0, -1, 0], "radius": 0.3, "material": "lamp" }
+        ]
+    }"#;
+
+    #[test]
+    fn test_load_scene() {
+        let file = SceneFile::from_json(SCENE, Path::new("")).unwrap();
+
+        assert_eq!(file.settings.width, 64);
+        assert_eq!(file.settings.height, RenderSettings::default().height);
+        assert_eq!(file.settings.samples, 4);
+        assert_eq!(file.scene.objects_count(), 2);
+        assert_eq!(file.scene.materials_count(), 2);
+        assert_eq!(file.camera.lookfrom, Vec3::new(0., 0., 3.));
+        assert!(relative_eq!(file.camera.aspect, 64. / 100.));
+        assert_eq!((file.camera.aperture, file.camera.focus_distance), (0., 3.));
+
+        let json = SCENE.replace(r#""vfov": 40"#, r#""vfov": 40, "aperture": 0.1, "focus_distance": 2"#);
+        let file = SceneFile::from_json(&json, Path::new("")).unwrap();
+        assert_eq!((file.camera.aperture, file.camera.focus_distance), (0.1, 2.));
+
+        let json = SCENE.replace(r#""vfov": 40"#, r#""vfov": 40, "projection": { "type": "fisheye", "fov": 190 }"#);
+        let file = SceneFile::from_json(&json, Path::new("")).unwrap();
+        assert_eq!(file.camera.projection, Projection::Fisheye { fov: 190. });
+    }
+
+    #[test]
+    fn test_unknown_material() {
+        let json = SCENE.replace(r#""radius": 0.5, "material": "white""#, r#""radius": 0.5, "material": "black""#);
+        match SceneFile::from_json(&json, Path::new("")) {
+            Err(SceneFileError::UnknownMaterial(name)) => assert_eq!(name, "black"),
+            _ => panic!("expected unknown material error"),
+        }
+    }
+
+    #[test]
+    fn test_analytic_primitives() {
+        let json = SCENE.replace(r#""type": "sphere", "center": [0, 0, 0], "radius": 0.5"#,
+                                 r#""type": "torus", "center": [0, 0, 0], "major_radius": 1, "minor_radius": 0.2"#);
+        let file = SceneFile::from_json(&json, Path::new("")).unwrap();
+        let torus = file.scene.to_objects().into_iter().any(|o| match o.primitive {
+            Primitive::Torus(t) => t.axis == Vec3::y(),
+            _ => false,
+        });
+        assert!(torus);
+
+        let json = SCENE.replace(r#""type": "sphere", "center": [0, 0, 0], "radius": 0.5"#,
+                                 r#""type": "sdf", "shape": { "type": "subtraction", "a": { "type": "box", "half_size": [1, 1, 1] },
+                                     "b": { "type": "sphere", "radius": 1.2 } }"#);
+        let file = SceneFile::from_json(&json, Path::new("")).unwrap();
+        let sdf = file.scene.to_objects().into_iter().any(|o| match o.primitive {
+            Primitive::Sdf(s) => s.distance(&Vec3::zeros()) > 0. && s.aabb().size() == Vec3::repeat(2.),
+            _ => false,
+        });
+        assert!(sdf);
+
+        let json = SCENE.replace(r#""type": "sphere", "center": [0, 0, 0], "radius": 0.5"#,
+                                 r#""type": "csg", "op": "difference", "a": { "type": "sphere", "center": [0, 0, 0], "radius": 1 },
+                                     "b": { "type": "cube", "center": [0, 0, 0], "size": [1, 1, 1], "transform": { "translate": [1, 1, 1] } }"#);
+        let file = SceneFile::from_json(&json, Path::new("")).unwrap();
+        let csg = file.scene.to_objects().into_iter().any(|o| match &o.primitive {
+            Primitive::Csg(c) => c.op == CsgOp::Difference && match *c.b { Primitive::Instance(_) => true, _ => false },
+            _ => false,
+        });
+        assert!(csg);
+
+        let json = SCENE.replace(r#""type": "sphere", "center": [0, 0, 0], "radius": 0.5"#,
+                                 r#""type": "csg", "op": "union", "a": { "type": "mesh", "path": "a.ply" }, "b": { "type": "sphere", "center": [0, 0, 0], "radius": 1 }"#);
+        match SceneFile::from_json(&json, Path::new("")) {
+            Err(SceneFileError::NotSolid) => {},
+            _ => panic!("expected not solid error"),
+        }
+
+        for child in &[r#"{ "type": "plane", "origin": [0, 0, 0], "normal": [0, 1, 0] }"#,
+                       r#"{ "type": "cylinder", "base": [0, 0, 0], "top": [0, 1, 0], "radius": 1, "capped": false }"#,
+                       r#"{ "type": "sdf", "shape": { "type": "sphere", "radius": 1 } }"#] {
+            let json = SCENE.replace(r#""type": "sphere", "center": [0, 0, 0], "radius": 0.5"#,
+                                     &format!(r#""type": "csg", "op": "intersection", "a": {}, "b": {{ "type": "sphere", "center": [0, 0, 0], "radius": 1 }}"#, child));
+            match SceneFile::from_json(&json, Path::new("")) {
+                Err(SceneFileError::NotSolid) => {},
+                _ => panic!("expected not solid error for {}", child),
+            }
+        }
+    }
+
+    #[test]
+    fn test_transformed_object() {
+        let json = SCENE.replace(r#""radius": 0.5, "material": "white""#,
+                                 r#""radius": 0.5, "material": "white", "transform": { "scale": [2, 1, 1] }"#);
+        let file = SceneFile::from_json(&json, Path::new("")).unwrap();
+
+        let instances = file.scene.to_objects().into_iter()
+            .filter(|o| match o.primitive { Primitive::Instance(_) => true, _ => false })
+            .count();
+        assert_eq!(instances, 1);
+    }
+
+    #[test]
+    fn test_motion_blur() {
+        let json = SCENE.replace(r#""radius": 0.5, "material": "white""#,
+                                 r#""radius": 0.5, "material": "white", "motion": [1, 0, 0]"#)
+            .replace(r#""vfov": 40"#, r#""vfov": 40, "shutter": [0, 0.5], "motion": [0, 0.1, 0]"#);
+        let file = SceneFile::from_json(&json, Path::new("")).unwrap();
+
+        assert_eq!(file.camera.shutter, (0., 0.5));
+        assert_eq!(file.camera.motion, Vec3::new(0., 0.1, 0.));
+        let moving = file.scene.to_objects().into_iter()
+            .filter(|o| match &o.primitive { Primitive::Instance(i) => i.motion == Vec3::x(), _ => false })
+            .count();
+        assert_eq!(moving, 1);
+    }
+
+    #[test]
+    fn test_textured_materials() {
+        let json = SCENE.replace(r#""albedo": [0.73, 0.73, 0.73]"#,
+                                 r#""albedo": { "type": "checker", "even": [1, 1, 1], "odd": [0, 0, 0], "scale": 4 }"#);
+        let file = SceneFile::from_json(&json, Path::new("")).unwrap();
+        let checker = file.scene.materials_iter().any(|(_, m)| match m {
+            Material::Lambertian(Lambertian { albedo: Texture::Checker(c) }) => c.scale == 4.,
+            _ => false,
+        });
+        assert!(checker);
+
+        let json = SCENE.replace(r#""albedo": [0.73, 0.73, 0.73]"#, r#""albedo": { "type": "image", "path": "missing.png" }"#);
+        match SceneFile::from_json(&json, Path::new("")) {
+            Err(SceneFileError::Texture(path, _)) => assert_eq!(path, Path::new("missing.png")),
+            _ => panic!("expected texture error"),
+        }
+    }
+
+    #[test]
+    fn test_environment() {
+        let file = SceneFile::from_json(SCENE, Path::new("")).unwrap();
+        match file.background {
+            Background::Color(color) => assert_eq!(color, Vec3::repeat(1.)),
+            _ => panic!("expected background color"),
+        }
+
+        let dir = std::env::temp_dir().join("rtracer_scene_file_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        crate::image::Image::with_background(4, 2, Vec3::repeat(0.5)).save(&dir.join("sky.hdr")).unwrap();
+
+        let json = SCENE.replace(r#""camera""#, r#""environment": { "type": "map", "path": "sky.hdr", "intensity": 2 }, "camera""#);
+        let file = SceneFile::from_json(&json, &dir).unwrap();
+        match file.background {
+            Background::Environment(map) => {
+                assert_eq!(map.intensity(), 2.);
+                assert!((map.radiance(&Vec3::z()) - Vec3::repeat(1.)).norm() < 0.05);
+            },
+            _ => panic!("expected environment map"),
+        }
+
+        let json = SCENE.replace(r#""camera""#, r#""environment": { "type": "sky", "sun_direction": [0, 1, 1] }, "camera""#);
+        match SceneFile::from_json(&json, Path::new("")).unwrap().background {
+            Background::Sky(sky) => assert_eq!(sky.turbidity(), 3.),
+            _ => panic!("expected sky"),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use crate::Vec3;
+use crate::ray::Ray;
+use crate::aabb::Aabb;
+use crate::intersect::Intersect;
+use crate::intersection::ray_aabb_intersection;
+use crate::bounded::Bounded;
+
+const MAX_STEPS: u32 = 256;
+// distance counted as surface
+const HIT_DISTANCE: f32 = 1e-4;
+// offset of central differences
+const NORMAL_EPSILON: f32 = 1e-4;
+
+// distance function expression, shapes are centered at origin, torus lies in xz plane
+#[derive(Clone, Debug)]
+pub enum SdfNode {
+    Sphere { radius: f32 },
+    Box { half_size: Vec3 },
+    // box with edges rounded by radius, radius is inside of half size
+    RoundedBox { half_size: Vec3, radius: f32 },
+    Torus { major_radius: f32, minor_radius: f32 },
+    Translate { offset: Vec3, node: Box<SdfNode> },
+    Union { a: Box<SdfNode>, b: Box<SdfNode> },
+    // blends shapes closer than k
+    SmoothUnion { a: Box<SdfNode>, b: Box<SdfNode>, k: f32 },
+    // a without b
+    Subtraction { a: Box<SdfNode>, b: Box<SdfNode> },
+    // count copies along each axis starting at origin, node must fit into spacing for exact distance
+    Repeat { spacing: Vec3, count: [u32; 3], node: Box<SdfNode> },
+}
+
+impl SdfNode {
+    // lower bound of distance to the surface, negative inside
+    pub fn distance(&self, p: &Vec3) -> f32 {
+        match self {
+            SdfNode::Sphere { radius } => p.norm() - radius,
+            SdfNode::Box { half_size } => box_distance(p, half_size),
+            SdfNode::RoundedBox { half_size, radius } => box_distance(p, &(half_size - Vec3::repeat(*radius))) - radius,
+            SdfNode::Torus { major_radius, minor_radius } => {
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            },
+            SdfNode::Translate { offset, node } => node.distance(&(p - offset)),
+            SdfNode::Union { a, b } => a.distance(p).min(b.distance(p)),
+            SdfNode::SmoothUnion { a, b, k } => {
+                let (da, db) = (a.distance(p), b.distance(p));
+                let h = (k - (da - db).abs()).max(0.) / k;
+                da.min(db) - h * h * k / 4.
+            },
+            SdfNode::Subtraction { a, b } => a.distance(p).max(-b.distance(p)),
+            SdfNode::Repeat { spacing, count, node } => {
+                let mut q = *p;
+                for i in 0..3 {
+                    if spacing[i] > 0. {
+                        let cell = (p[i] / spacing[i]).round().max(0.).min(count[i].saturating_sub(1) as f32);
+                        q[i] -= cell * spacing[i];
+                    }
+                }
+                node.distance(&q)
+            },
+        }
+    }
+}
+
+// conservative bounds of the surface
+impl Bounded for SdfNode {
+    fn aabb(&self) -> Aabb {
+        match self {
+            SdfNode::Sphere { radius } => Aabb::from_center_size(Vec3::zeros(), Vec3::repeat(2. * radius)),
+            SdfNode::Box { half_size } | SdfNode::RoundedBox { half_size, .. } => {
+                Aabb::from_center_size(Vec3::zeros(), 2. * half_size)
+            },
+            SdfNode::Torus { major_radius, minor_radius } => {
+                let r = major_radius + minor_radius;
+                Aabb::from_center_size(Vec3::zeros(), 2. * Vec3::new(r, *minor_radius, r))
+            },
+            SdfNode::Translate { offset, node } => {
+                let aabb = node.aabb();
+                Aabb::new(aabb.min + offset, aabb.max + offset)
+            },
+            SdfNode::Union { a, b } => Aabb::union(&a.aabb(), &b.aabb()),
+            // blend grows the surface by at most k / 4
+            SdfNode::SmoothUnion { a, b, k } => {
+                let aabb = Aabb::union(&a.aabb(), &b.aabb());
+                Aabb::new(aabb.min - Vec3::repeat(k / 4.), aabb.max + Vec3::repeat(k / 4.))
+            },
+            SdfNode::Subtraction { a, .. } => a.aabb(),
+            SdfNode::Repeat { spacing, count, node } => {
+                let aabb = node.aabb();
+                let last = Vec3::new(spacing.x * count[0].saturating_sub(1) as f32,
+                                     spacing.y * count[1].saturating_sub(1) as f32,
+                                     spacing.z * count[2].saturating_sub(1) as f32);
+                Aabb::new(aabb.min, aabb.max + last)
+            },
+        }
+    }
+}
+
+fn box_distance(p: &Vec3, half_size: &Vec3) -> f32 {
+    let q = p.abs() - half_size;
+    q.map(|c| c.max(0.)).norm() + q.max().min(0.)
+}
+
+// surface of distance function, intersected by sphere tracing inside of its bounds
+#[derive(Clone, Debug)]
+pub struct Sdf {
+    root: SdfNode,
+    aabb: Aabb,
+}
+
+impl Sdf {
+    pub fn new(root: SdfNode) -> Sdf {
+        let aabb = root.aabb();
+        Sdf { root, aabb }
+    }
+
+    pub fn root(&self) -> &SdfNode {
+        &self.root
+    }
+
+    pub fn distance(&self, p: &Vec3) -> f32 {
+        self.root.distance(p)
+    }
+
+    // gradient by central differences
+    pub fn normal_at(&self, p: &Vec3) -> Vec3 {
+        let mut normal = Vec3::zeros();
+        for i in 0..3 {
+            let mut offset = Vec3::zeros();
+            offset[i] = NORMAL_EPSILON;
+            normal[i] = self.distance(&(p + offset)) - self.distance(&(p - offset));
+        }
+        normal.try_normalize(0.).unwrap_or_else(Vec3::y)
+    }
+}
+
+impl Intersect for Sdf {
+    fn intersect(&self, ray: &Ray, (t_min, t_max): (f32, f32)) -> Option<f32> {
+        let (near, far) = ray_aabb_intersection(ray, &self.aabb)?;
+        let far = far.min(t_max);
+        // directions of rays transformed by instances are not normalized
+        let speed = ray.direction.norm();
+
+        let mut t = near.max(t_min);
+        // inside rays march to the surface too
+        let sign = self.distance(&ray.point_at_parameter(t)).signum();
+        for _ in 0..MAX_STEPS {
+            if t > far {
+                return None
+            }
+            let d = sign * self.distance(&ray.point_at_parameter(t));
+            if d < HIT_DISTANCE {
+                return if t > t_min { Some(t) } else { None }
+            }
+            t += d / speed;
+        }
+
+        None
+    }
+}
+
+impl Bounded for Sdf {
+    fn aabb(&self) -> Aabb {
+        self.aabb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sphere(radius: f32, x: f32) -> Box<SdfNode> {
+        Box::new(SdfNode::Translate { offset: Vec3::new(x, 0., 0.), node: Box::new(SdfNode::Sphere { radius }) })
+    }
+
+    #[test]
+    fn test_sphere_tracing() {
+        let sdf = Sdf::new(*sphere(1., 0.));
+        let ray = Ray::new(Vec3::new(0., 0., 5.), -Vec3::z());
+        let t = sdf.intersect(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 4., epsilon = 1e-3));
+        assert!((sdf.normal_at(&ray.point_at_parameter(t)) - Vec3::z()).norm() < 1e-3);
+        assert!(sdf.intersect(&ray, (0., 3.)).is_none());
+        assert!(sdf.intersect(&Ray::new(Vec3::new(0., 1.1, 5.), -Vec3::z()), (0., std::f32::MAX)).is_none());
+
+        // from inside
+        let t = sdf.intersect(&Ray::new(Vec3::new(0., 0., 0.5), Vec3::z()), (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 0.5, epsilon = 1e-3));
+
+        let rounded = Sdf::new(SdfNode::RoundedBox { half_size: Vec3::repeat(1.), radius: 0.2 });
+        let t = rounded.intersect(&Ray::new(Vec3::new(0.5, 0.5, 5.), -Vec3::z()), (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 4., epsilon = 1e-3));
+        // corner is cut
+        assert!(rounded.intersect(&Ray::new(Vec3::new(0.98, 0.98, 5.), -Vec3::z()), (0., std::f32::MAX)).is_none());
+    }
+
+    #[test]
+    fn test_operations() {
+        // smooth union fills the gap between spheres
+        let blend = Sdf::new(SdfNode::SmoothUnion { a: sphere(0.5, -0.6), b: sphere(0.5, 0.6), k: 0.5 });
+        assert!(blend.distance(&Vec3::zeros()) < 0.);
+        assert!(Sdf::new(SdfNode::Union { a: sphere(0.5, -0.6), b: sphere(0.5, 0.6) }).distance(&Vec3::zeros()) > 0.);
+
+        let hollow = Sdf::new(SdfNode::Subtraction { a: Box::new(SdfNode::Box { half_size: Vec3::repeat(1.) }), b: sphere(0.5, 1.) });
+        let t = hollow.intersect(&Ray::new(Vec3::new(5., 0., 0.), -Vec3::x()), (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 4.5, epsilon = 1e-3));
+
+        let row = Sdf::new(SdfNode::Repeat { spacing: Vec3::new(3., 0., 0.), count: [3, 1, 1], node: sphere(1., 0.) });
+        let hits = [0., 3., 6., 9.].iter()
+            .filter(|x| row.intersect(&Ray::new(Vec3::new(**x, 0., 5.), -Vec3::z()), (0., std::f32::MAX)).is_some())
+            .count();
+        assert_eq!(hits, 3);
+
+        let aabb = row.aabb();
+        assert!((aabb.min - Vec3::new(-1., -1., -1.)).norm() < 1e-6);
+        assert!((aabb.max - Vec3::new(7., 1., 1.)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn test_torus() {
+        let torus = Sdf::new(SdfNode::Torus { major_radius: 1., minor_radius: 0.25 });
+        assert!(torus.intersect(&Ray::new(Vec3::new(0., 5., 0.), -Vec3::y()), (0., std::f32::MAX)).is_none());
+        let t = torus.intersect(&Ray::new(Vec3::new(1., 5., 0.), -Vec3::y()), (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 4.75, epsilon = 1e-3));
+    }
+}
@@ -0,0 +1,249 @@
+use std::f32::consts::PI;
+
+use crate::{Vec3, Vec2, Mat3};
+use crate::image::Image;
+use crate::environment::{EnvironmentMap, equirect_direction};
+
+// angular radius of the sun seen from earth
+const SUN_ANGULAR_RADIUS: f32 = 0.004_65;
+// luminance of the sun outside of atmosphere, kcd/m2 like the sky model
+const SUN_LUMINANCE: f32 = 1.6e6;
+// preetham luminance is in kcd/m2, this brings clear sky near zenith to about 1
+const SKY_SCALE: f32 = 0.1;
+const GROUND_ALBEDO: f32 = 0.3;
+// resolution of the map used for importance sampling of the sky dome
+const MAP_SIZE: (u32, u32) = (128, 64);
+
+// preetham, shirley, smits "a practical analytic model for daylight", sky dome with sun disk, y is up
+pub struct Sky {
+    sun_direction: Vec3,
+    turbidity: f32,
+    intensity: f32,
+    dome: Dome,
+    sun_radiance: Vec3,
+    // dome without the sun, the sun is sampled as a cone
+    map: EnvironmentMap,
+}
+
+impl Sky {
+    // turbidity is in [1.7, 10], from very clear to hazy sky
+    pub fn new(sun_direction: Vec3, turbidity: f32, intensity: f32) -> Sky {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity.clamp(1.7, 10.);
+
+        let dome = Dome::new(&sun_direction, t);
+
+        let sun_radiance = if sun_direction.y > 0. {
+            SUN_LUMINANCE * SKY_SCALE * sun_transmittance(sun_direction.y.acos(), t)
+        } else {
+            Vec3::zeros()
+        };
+
+        let (width, height) = MAP_SIZE;
+        let mut image = Image::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let uv = Vec2::new((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32);
+                image[(x, y)] = dome.radiance(&equirect_direction(&uv, 0.));
+            }
+        }
+        let map = EnvironmentMap::new(image, 0., intensity);
+
+        Sky { sun_direction, turbidity: t, intensity, dome, sun_radiance, map }
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    pub fn turbidity(&self) -> f32 {
+        self.turbidity
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    // baked dome without the sun, for consumers that can only draw images, e.g. gpu renderer
+    pub fn environment_map(&self) -> &EnvironmentMap {
+        &self.map
+    }
+
+    pub fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let direction = direction.normalize();
+        let mut radiance = self.dome.radiance(&direction);
+        if self.in_sun(&direction) {
+            radiance += self.sun_radiance;
+        }
+        radiance * self.intensity
+    }
+
+    // direction with its solid angle pdf, half of samples go to the sun when it is up
+    pub fn sample(&self, (u1, u2): (f32, f32)) -> Option<(Vec3, f32)> {
+        let p_sun = self.sun_probability();
+        let direction = if u1 < p_sun {
+            self.sample_sun((u1 / p_sun, u2))
+        } else {
+            self.map.sample(((u1 - p_sun) / (1. - p_sun), u2))?.0
+        };
+
+        let pdf = self.pdf(&direction);
+        if pdf > 0. { Some((direction, pdf)) } else { None }
+    }
+
+    pub fn pdf(&self, direction: &Vec3) -> f32 {
+        let p_sun = self.sun_probability();
+        let sun_pdf = if self.in_sun(&direction.normalize()) { 1. / (2. * PI * sun_one_minus_cos()) } else { 0. };
+        p_sun * sun_pdf + (1. - p_sun) * self.map.pdf(direction)
+    }
+
+    fn sun_probability(&self) -> f32 {
+        if self.sun_radiance == Vec3::zeros() { 0. } else { 0.5 }
+    }
+
+    fn in_sun(&self, direction: &Vec3) -> bool {
+        1. - Vec3::dot(direction, &self.sun_direction) <= sun_one_minus_cos()
+    }
+
+    fn sample_sun(&self, (u1, u2): (f32, f32)) -> Vec3 {
+        // 1 - cos is kept instead of cos, f32 cant represent cosines that close to 1
+        let one_minus_cos = u1 * sun_one_minus_cos();
+        let cos_theta = 1. - one_minus_cos;
+        let sin_theta = (one_minus_cos * (2. - one_minus_cos)).sqrt();
+        let phi = 2. * PI * u2;
+
+        let n = self.sun_direction;
+        let a = if n.x.abs() > 0.9 { Vec3::y() } else { Vec3::x() };
+        let t = n.cross(&a).normalize();
+        let b = n.cross(&t);
+        (sin_theta * phi.cos() * t + sin_theta * phi.sin() * b + cos_theta * n).normalize()
+    }
+}
+
+// sky without the sun
+struct Dome {
+    sun_direction: Vec3,
+    theta_s: f32,
+    // perez coefficients A..E of luminance and x, y chromaticity
+    perez: [[f32; 5]; 3],
+    // luminance and chromaticity at zenith
+    zenith: [f32; 3],
+}
+
+impl Dome {
+    fn new(sun_direction: &Vec3, t: f32) -> Dome {
+        // the model is valid for the sun above horizon only
+        let theta_s = sun_direction.y.clamp(0.01, 1.).acos();
+
+        let perez = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let ts = Vec3::new(t * t, t, 1.);
+        let thetas = na::Vector4::new(theta_s.powi(3), theta_s * theta_s, theta_s, 1.);
+        let mx = na::Matrix3x4::new(0.00166, -0.00375, 0.00209, 0.,
+                                    -0.02903, 0.06377, -0.03202, 0.00394,
+                                    0.11693, -0.21196, 0.06052, 0.25886);
+        let my = na::Matrix3x4::new(0.00275, -0.00610, 0.00317, 0.,
+                                    -0.04214, 0.08970, -0.04153, 0.00516,
+                                    0.15346, -0.26756, 0.06670, 0.26688);
+        let zenith = [zenith_luminance, ts.dot(&(mx * thetas)), ts.dot(&(my * thetas))];
+
+        Dome { sun_direction: *sun_direction, theta_s, perez, zenith }
+    }
+
+    // ground below horizon reflects the sky above
+    fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let (direction, albedo) = if direction.y < 0. {
+            (Vec3::new(direction.x, -direction.y, direction.z), GROUND_ALBEDO)
+        } else {
+            (*direction, 1.)
+        };
+
+        let gamma = Vec3::dot(&direction, &self.sun_direction).clamp(-1., 1.).acos();
+
+        let mut value = [0.; 3];
+        for (i, value) in value.iter_mut().enumerate() {
+            *value = self.zenith[i] * perez(&self.perez[i], direction.y, gamma) / perez(&self.perez[i], 1., self.theta_s);
+        }
+
+        xyy_to_rgb(value[0] * SKY_SCALE, value[1], value[2]) * albedo
+    }
+}
+
+fn sun_one_minus_cos() -> f32 {
+    2. * (SUN_ANGULAR_RADIUS / 2.).sin().powi(2)
+}
+
+fn perez(c: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    (1. + c[0] * (c[1] / cos_theta.max(1e-3)).exp()) * (1. + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos().powi(2))
+}
+
+// rayleigh and aerosol extinction along the path of sunlight at red, green and blue wavelengths
+fn sun_transmittance(theta: f32, turbidity: f32) -> Vec3 {
+    let relative_mass = 1. / (theta.cos() + 0.15 * (93.885 - theta.to_degrees()).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+
+    let channel = |lambda: f32| {
+        let rayleigh = 0.008_735 * lambda.powf(-4.08);
+        let aerosol = beta * lambda.powf(-1.3);
+        (-relative_mass * (rayleigh + aerosol)).exp()
+    };
+
+    Vec3::new(channel(0.68), channel(0.55), channel(0.44))
+}
+
+// linear srgb primaries
+fn xyy_to_rgb(luminance: f32, x: f32, y: f32) -> Vec3 {
+    if y <= 0. {
+        return Vec3::zeros()
+    }
+    let xyz = Vec3::new(x * luminance / y, luminance, (1. - x - y) * luminance / y);
+    let m = Mat3::new(3.2406, -1.5372, -0.4986,
+                      -0.9689, 1.8758, 0.0415,
+                      0.0557, -0.2040, 1.0570);
+    (m * xyz).map(|c| c.max(0.))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sky_colors() {
+        let sky = Sky::new(Vec3::new(0., 1., -1.), 3., 1.);
+
+        // blue zenith, sun much brighter than the sky
+        let zenith = sky.radiance(&Vec3::y());
+        assert!(zenith.z > zenith.x && zenith.x > 0.);
+        assert!(sky.radiance(&sky.sun_direction()).y > 1000. * zenith.y);
+        // sky is brighter around the sun
+        assert!(sky.radiance(&Vec3::new(0., 0.8, -1.)).y > sky.radiance(&Vec3::new(0., 0.8, 1.)).y);
+
+        // sunset light is red
+        let low = Sky::new(Vec3::new(0., 0.05, -1.), 3., 1.);
+        let sun = low.radiance(&low.sun_direction());
+        assert!(sun.x > sun.z);
+    }
+
+    #[test]
+    fn test_sample_pdf() {
+        let sky = Sky::new(Vec3::new(1., 1., 0.), 4., 1.);
+        let n = 256;
+        let mut sun_samples = 0;
+        for i in 0..n {
+            let u = ((i as f32 + 0.5) / n as f32, (i * 37 % n) as f32 / n as f32);
+            let (direction, pdf) = sky.sample(u).unwrap();
+            assert!(relative_eq!(pdf, sky.pdf(&direction), max_relative = 1e-3), "{} != {}", pdf, sky.pdf(&direction));
+            if sky.in_sun(&direction) {
+                sun_samples += 1;
+            }
+        }
+        assert!(sun_samples >= n / 2);
+    }
+}
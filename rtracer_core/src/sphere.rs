@@ -1,4 +1,6 @@
-use crate::Vec3;
+use std::f32::consts::PI;
+
+use crate::{Vec3, Vec2};
 use crate::ray::Ray;
 use crate::aabb::Aabb;
 use crate::intersect::Intersect;
@@ -7,23 +9,48 @@ use crate::bounded::Bounded;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Sphere {
+    // at time 0
     pub center: Vec3,
     pub radius: f32,
+    // offset of the center at time 1
+    pub motion: Vec3,
 }
 
 impl Sphere {
     pub fn new(center: Vec3, radius: f32) -> Sphere {
-        Sphere { center, radius }
+        Sphere { center, radius, motion: Vec3::zeros() }
+    }
+
+    // moves linearly from center0 at time 0 to center1 at time 1
+    pub fn moving(center0: Vec3, center1: Vec3, radius: f32) -> Sphere {
+        Sphere { center: center0, radius, motion: center1 - center0 }
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.motion != Vec3::zeros()
+    }
+
+    // static sphere at the position of the moment
+    pub fn at_time(&self, time: f32) -> Sphere {
+        Sphere::new(self.center + self.motion * time, self.radius)
     }
 
     pub fn normal_at(&self, point: &Vec3) -> Vec3 {
         (*point - self.center).normalize()
     }
+
+    // longitude around y axis and latitude from the bottom, both in [0, 1]
+    pub fn uv_at(&self, point: &Vec3) -> Vec2 {
+        let n = (*point - self.center).normalize();
+        let phi = n.z.atan2(n.x);
+        let theta = n.y.max(-1.).min(1.).asin();
+        Vec2::new(0.5 - phi / (2. * PI), 0.5 + theta / PI)
+    }
 }
 
 impl Intersect for Sphere {
     fn intersect(&self, ray: &Ray, (t_min, t_max): (f32, f32)) -> Option<f32> {
-        if let Some(t) = ray_sphere_intersection(ray, self) {
+        if let Some(t) = ray_sphere_intersection(ray, &self.at_time(ray.time)) {
             if t_min < t && t < t_max {
                 return Some(t)
             }
@@ -35,7 +62,27 @@ impl Intersect for Sphere {
 
 
 impl Bounded for Sphere {
+    // swept over whole motion
     fn aabb(&self) -> Aabb {
-        Aabb::from_center_size(self.center, Vec3::from_element(self.radius * 2.))
+        let size = Vec3::from_element(self.radius * 2.);
+        Aabb::union(&Aabb::from_center_size(self.center, size), &Aabb::from_center_size(self.center + self.motion, size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moving_sphere() {
+        let sphere = Sphere::moving(Vec3::zeros(), Vec3::new(2., 0., 0.), 0.5);
+        let ray = |time| Ray::with_time(Vec3::new(2., 0., 5.), -Vec3::z(), time);
+
+        assert!(sphere.intersect(&ray(0.), (0., std::f32::MAX)).is_none());
+        assert!(relative_eq!(sphere.intersect(&ray(1.), (0., std::f32::MAX)).unwrap(), 4.5, epsilon = 1e-5));
+
+        let aabb = sphere.aabb();
+        assert!((aabb.min - Vec3::new(-0.5, -0.5, -0.5)).norm() < 1e-6);
+        assert!((aabb.max - Vec3::new(2.5, 0.5, 0.5)).norm() < 1e-6);
     }
 }
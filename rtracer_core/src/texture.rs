@@ -0,0 +1,348 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{Vec3, Vec2};
+use crate::image::{Image, ImageError};
+
+// color looked up by surface uv or by world position for solid textures
+#[derive(Clone)]
+pub enum Texture {
+    Constant(Vec3),
+    Checker(Checker),
+    Noise(Noise),
+    Image(ImageTexture),
+}
+
+impl Texture {
+    pub fn value(&self, uv: &Vec2, point: &Vec3) -> Vec3 {
+        match self {
+            Texture::Constant(color) => *color,
+            Texture::Checker(t) => t.value(uv),
+            Texture::Noise(t) => t.value(point),
+            Texture::Image(t) => t.value(uv),
+        }
+    }
+
+    // single color for consumers without texture support, e.g. gpu renderer
+    pub fn average(&self) -> Vec3 {
+        match self {
+            Texture::Constant(color) => *color,
+            Texture::Checker(t) => (t.even + t.odd) / 2.,
+            Texture::Noise(t) => t.color / 2.,
+            Texture::Image(t) => {
+                let buf = t.image.buf();
+                buf.iter().fold(Vec3::zeros(), |sum, c| sum + c) / buf.len().max(1) as f32
+            },
+        }
+    }
+}
+
+impl From<Vec3> for Texture {
+    fn from(color: Vec3) -> Self {
+        Texture::Constant(color)
+    }
+}
+
+impl From<Checker> for Texture {
+    fn from(t: Checker) -> Self {
+        Texture::Checker(t)
+    }
+}
+
+impl From<Noise> for Texture {
+    fn from(t: Noise) -> Self {
+        Texture::Noise(t)
+    }
+}
+
+impl From<ImageTexture> for Texture {
+    fn from(t: ImageTexture) -> Self {
+        Texture::Image(t)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Checker {
+    pub even: Vec3,
+    pub odd: Vec3,
+    // squares per unit of uv
+    pub scale: f32,
+}
+
+impl Checker {
+    pub fn new(even: Vec3, odd: Vec3, scale: f32) -> Checker {
+        Checker { even, odd, scale }
+    }
+
+    pub fn value(&self, uv: &Vec2) -> Vec3 {
+        let square = (uv.x * self.scale).floor() + (uv.y * self.scale).floor();
+        if square.rem_euclid(2.) == 0. {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+// improved perlin noise, permutation is generated from seed
+pub struct Perlin {
+    // duplicated to skip wrapping of indices
+    perm: Vec<u8>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut perm: Vec<u8> = (0..=255).collect();
+
+        // xorshift is enough to shuffle the table
+        let mut state = seed ^ 0x9e37_79b9_7f4a_7c15;
+        for i in (1..perm.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            perm.swap(i, (state % (i as u64 + 1)) as usize);
+        }
+
+        let copy = perm.clone();
+        perm.extend(copy);
+        Perlin { perm }
+    }
+
+    // roughly in [-1, 1], zero at integer points
+    pub fn noise(&self, p: &Vec3) -> f32 {
+        let floor = p.map(|c| c.floor());
+        let f = p - floor;
+        let [x, y, z] = [floor.x as i32 & 255, floor.y as i32 & 255, floor.z as i32 & 255];
+        let (u, v, w) = (fade(f.x), fade(f.y), fade(f.z));
+
+        let perm = |i: i32| i32::from(self.perm[i as usize]);
+        let a = perm(x) + y;
+        let (aa, ab) = (perm(a) + z, perm(a + 1) + z);
+        let b = perm(x + 1) + y;
+        let (ba, bb) = (perm(b) + z, perm(b + 1) + z);
+
+        let grad = |hash: i32, dx: f32, dy: f32, dz: f32| grad(perm(hash), f.x - dx, f.y - dy, f.z - dz);
+
+        lerp(w, lerp(v, lerp(u, grad(aa, 0., 0., 0.), grad(ba, 1., 0., 0.)),
+                        lerp(u, grad(ab, 0., 1., 0.), grad(bb, 1., 1., 0.))),
+                lerp(v, lerp(u, grad(aa + 1, 0., 0., 1.), grad(ba + 1, 1., 0., 1.)),
+                        lerp(u, grad(ab + 1, 0., 1., 1.), grad(bb + 1, 1., 1., 1.))))
+    }
+
+    // fractal brownian motion, every octave doubles frequency and halves amplitude
+    pub fn fbm(&self, p: &Vec3, octaves: u32) -> f32 {
+        let (mut sum, mut norm) = (0., 0.);
+        let (mut amplitude, mut frequency) = (1., 1.);
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * self.noise(&(p * frequency));
+            norm += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.;
+        }
+        sum / norm
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+// one of 12 cube edge directions picked by hash
+fn grad(hash: i32, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+// solid texture, evaluated at world position
+#[derive(Clone)]
+pub struct Noise {
+    perlin: Arc<Perlin>,
+    pub color: Vec3,
+    // noise frequency in world units
+    pub scale: f32,
+    pub octaves: u32,
+}
+
+impl Noise {
+    pub fn new(color: Vec3, scale: f32, octaves: u32) -> Noise {
+        Noise::with_seed(color, scale, octaves, 0)
+    }
+
+    pub fn with_seed(color: Vec3, scale: f32, octaves: u32, seed: u64) -> Noise {
+        Noise { perlin: Arc::new(Perlin::new(seed)), color, scale, octaves }
+    }
+
+    pub fn value(&self, point: &Vec3) -> Vec3 {
+        let n = self.perlin.fbm(&(point * self.scale), self.octaves);
+        self.color * (0.5 * (n + 1.)).clamp(0., 1.)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+impl WrapMode {
+    fn wrap(self, i: i64, size: u32) -> u32 {
+        let size = i64::from(size);
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size { i } else { 2 * size - 1 - i }
+            },
+            WrapMode::Clamp => i.max(0).min(size - 1),
+        };
+        i as u32
+    }
+}
+
+impl Default for WrapMode {
+    fn default() -> WrapMode {
+        WrapMode::Repeat
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::Bilinear
+    }
+}
+
+// v goes from bottom to top of the image, like in obj and most modeling tools
+#[derive(Clone)]
+pub struct ImageTexture {
+    image: Arc<Image>,
+    wrap: WrapMode,
+    filter: Filter,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>) -> ImageTexture {
+        debug_assert!(image.width() > 0 && image.height() > 0);
+        ImageTexture { image, wrap: WrapMode::default(), filter: Filter::default() }
+    }
+
+    pub fn load(path: &Path) -> Result<ImageTexture, ImageError> {
+        Ok(ImageTexture::new(Arc::new(Image::load(path)?)))
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    pub fn wrap(&self) -> WrapMode {
+        self.wrap
+    }
+
+    pub fn set_wrap(&mut self, wrap: WrapMode) {
+        self.wrap = wrap;
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    pub fn value(&self, uv: &Vec2) -> Vec3 {
+        let (width, height) = (self.image.width() as f32, self.image.height() as f32);
+        let x = uv.x * width;
+        let y = (1. - uv.y) * height;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // texel centers are at half integer coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = self.texel(x0, y0) * (1. - fx) + self.texel(x0 + 1, y0) * fx;
+                let bottom = self.texel(x0, y0 + 1) * (1. - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+                top * (1. - fy) + bottom * fy
+            },
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec3 {
+        self.image[(self.wrap.wrap(x, self.image.width()), self.wrap.wrap(y, self.image.height()))]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checker() {
+        let checker = Checker::new(Vec3::zeros(), Vec3::repeat(1.), 2.);
+        assert_eq!(checker.value(&Vec2::new(0.1, 0.1)), Vec3::zeros());
+        assert_eq!(checker.value(&Vec2::new(0.6, 0.1)), Vec3::repeat(1.));
+        assert_eq!(checker.value(&Vec2::new(-0.1, 0.1)), Vec3::repeat(1.));
+        assert_eq!(checker.value(&Vec2::new(0.6, 0.6)), Vec3::zeros());
+    }
+
+    #[test]
+    fn test_perlin() {
+        let perlin = Perlin::new(7);
+        assert_eq!(perlin.noise(&Vec3::new(3., -2., 5.)), 0.);
+
+        let mut min_max = (0f32, 0f32);
+        for i in 0..1000 {
+            let p = Vec3::new(i as f32 * 0.37, i as f32 * 0.11, i as f32 * -0.23);
+            let n = perlin.fbm(&p, 4);
+            assert!(n.abs() <= 1.1);
+            min_max = (min_max.0.min(n), min_max.1.max(n));
+        }
+        assert!(min_max.0 < -0.2 && min_max.1 > 0.2);
+
+        let p = Vec3::new(0.3, 0.5, 0.7);
+        assert_eq!(perlin.noise(&p), Perlin::new(7).noise(&p));
+        assert_ne!(perlin.noise(&p), Perlin::new(8).noise(&p));
+    }
+
+    #[test]
+    fn test_image_wrap_and_filter() {
+        // black left column, white right column
+        let mut image = Image::new(2, 1);
+        image[(1, 0)] = Vec3::repeat(1.);
+        let mut texture = ImageTexture::new(Arc::new(image));
+
+        assert!((texture.value(&Vec2::new(0.5, 0.5)) - Vec3::repeat(0.5)).norm() < 1e-6);
+        assert!((texture.value(&Vec2::new(0.25, 0.5)) - Vec3::zeros()).norm() < 1e-6);
+        // repeat blends right border with left one
+        assert!((texture.value(&Vec2::new(1., 0.5)) - Vec3::repeat(0.5)).norm() < 1e-6);
+
+        texture.set_wrap(WrapMode::Clamp);
+        assert!((texture.value(&Vec2::new(1., 0.5)) - Vec3::repeat(1.)).norm() < 1e-6);
+        assert!((texture.value(&Vec2::new(-3., 0.5)) - Vec3::zeros()).norm() < 1e-6);
+
+        texture.set_wrap(WrapMode::Mirror);
+        texture.set_filter(Filter::Nearest);
+        assert_eq!(texture.value(&Vec2::new(1.2, 0.5)), Vec3::repeat(1.));
+        assert_eq!(texture.value(&Vec2::new(1.7, 0.5)), Vec3::zeros());
+    }
+}
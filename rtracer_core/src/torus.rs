@@ -0,0 +1,79 @@
+use crate::Vec3;
+use crate::ray::Ray;
+use crate::aabb::Aabb;
+use crate::intersect::Intersect;
+use crate::intersection::{ray_torus_crossings, nearest_crossing};
+use crate::bounded::Bounded;
+use crate::disk::disk_extent;
+
+// ring around axis through center, tube of minor radius around circle of major radius
+#[derive(Copy, Clone, Debug)]
+pub struct Torus {
+    pub center: Vec3,
+    pub axis: Vec3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+impl Torus {
+    pub fn new(center: Vec3, axis: Vec3, major_radius: f32, minor_radius: f32) -> Torus {
+        Torus { center, axis: axis.normalize(), major_radius, minor_radius }
+    }
+
+    pub fn normal_at(&self, point: &Vec3) -> Vec3 {
+        let q = point - self.center;
+        let in_plane = q - Vec3::dot(&q, &self.axis) * self.axis;
+        let ring_point = in_plane.try_normalize(0.).unwrap_or_else(Vec3::zeros) * self.major_radius;
+        (q - ring_point).normalize()
+    }
+}
+
+impl Intersect for Torus {
+    fn intersect(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<f32> {
+        nearest_crossing(&ray_torus_crossings(ray, self), t_min_max)
+    }
+}
+
+impl Bounded for Torus {
+    fn aabb(&self) -> Aabb {
+        let e = disk_extent(&self.axis, self.major_radius) + Vec3::repeat(self.minor_radius);
+        Aabb::new(self.center - e, self.center + e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_torus_hits() {
+        let torus = Torus::new(Vec3::new(0., 1., 0.), Vec3::y(), 1., 0.25);
+
+        // through the hole along the axis
+        assert!(torus.intersect(&Ray::new(Vec3::new(0., 5., 0.), -Vec3::y()), (0., std::f32::MAX)).is_none());
+
+        // outer side, then from inside of the tube
+        let ray = Ray::new(Vec3::new(5., 1., 0.), -Vec3::x());
+        let t = torus.intersect(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 3.75, epsilon = 1e-4));
+        assert!((torus.normal_at(&ray.point_at_parameter(t)) - Vec3::x()).norm() < 1e-4);
+        assert!(relative_eq!(torus.intersect(&ray, (4., std::f32::MAX)).unwrap(), 4.25, epsilon = 1e-4));
+        let ray = Ray::new(Vec3::new(1., 1., 0.), -Vec3::x());
+        assert!(relative_eq!(torus.intersect(&ray, (0., std::f32::MAX)).unwrap(), 0.25, epsilon = 1e-4));
+
+        // top of the tube from above
+        let ray = Ray::new(Vec3::new(0., 4., -1.), -Vec3::y());
+        let t = torus.intersect(&ray, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 2.75, epsilon = 1e-4));
+        assert!((torus.normal_at(&ray.point_at_parameter(t)) - Vec3::y()).norm() < 1e-4);
+
+        // grazing the tube far away
+        let far = Ray::new(Vec3::new(1000., 1.2, 0.), -Vec3::x());
+        let t = torus.intersect(&far, (0., std::f32::MAX)).unwrap();
+        assert!(relative_eq!(t, 1000. - 1. - (0.25f32 * 0.25 - 0.04).sqrt(), epsilon = 1e-3));
+
+        let aabb = Torus::new(Vec3::zeros(), Vec3::z(), 1., 0.25).aabb();
+        assert!((aabb.min - Vec3::new(-1.25, -1.25, -0.25)).norm() < 1e-6);
+        assert!((aabb.max - Vec3::new(1.25, 1.25, 0.25)).norm() < 1e-6);
+    }
+}
@@ -0,0 +1,139 @@
+use na::{Rotation3, Unit, Vector4};
+
+use crate::{Vec3, Mat4};
+use crate::ray::Ray;
+use crate::aabb::Aabb;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform { matrix: Mat4::identity(), inverse: Mat4::identity() }
+    }
+
+    // None for degenerate matrices, e.g. zero scale
+    pub fn new(matrix: Mat4) -> Option<Transform> {
+        matrix.try_inverse().map(|inverse| Transform { matrix, inverse })
+    }
+
+    pub fn translation(offset: Vec3) -> Transform {
+        Transform { matrix: Mat4::new_translation(&offset), inverse: Mat4::new_translation(&-offset) }
+    }
+
+    pub fn scale(scale: Vec3) -> Transform {
+        debug_assert!(scale.iter().all(|s| *s != 0.));
+        let inverse = Vec3::new(1. / scale.x, 1. / scale.y, 1. / scale.z);
+        Transform { matrix: Mat4::new_nonuniform_scaling(&scale), inverse: Mat4::new_nonuniform_scaling(&inverse) }
+    }
+
+    // angle in degrees, like camera fov
+    pub fn rotation(axis: Vec3, angle: f32) -> Transform {
+        let rotation = Rotation3::from_axis_angle(&Unit::new_normalize(axis), angle.to_radians());
+        Transform { matrix: rotation.to_homogeneous(), inverse: rotation.inverse().to_homogeneous() }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform { matrix: self.inverse, inverse: self.matrix }
+    }
+
+    // self is applied first
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform { matrix: other.matrix * self.matrix, inverse: self.inverse * other.inverse }
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        apply(&self.matrix, p, 1.)
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        apply(&self.matrix, v, 0.)
+    }
+
+    // normals are transformed by inverse transpose to stay perpendicular to surface
+    pub fn transform_normal(&self, n: &Vec3) -> Vec3 {
+        apply(&self.inverse.transpose(), n, 0.).normalize()
+    }
+
+    pub fn inverse_transform_point(&self, p: &Vec3) -> Vec3 {
+        apply(&self.inverse, p, 1.)
+    }
+
+    // direction is not normalized, so t along the ray is the same in both spaces
+    pub fn inverse_transform_ray(&self, ray: &Ray) -> Ray {
+        Ray { origin: apply(&self.inverse, &ray.origin, 1.), direction: apply(&self.inverse, &ray.direction, 0.), time: ray.time }
+    }
+
+    pub fn transform_aabb(&self, aabb: &Aabb) -> Aabb {
+        // corners at infinity would give nan
+        if !aabb.is_finite() {
+            return Aabb::infinite()
+        }
+
+        let mut result = Aabb::empty();
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z });
+            result.add_point(&self.transform_point(&corner));
+        }
+        result
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+fn apply(m: &Mat4, v: &Vec3, w: f32) -> Vec3 {
+    let h = m * Vector4::new(v.x, v.y, v.z, w);
+    Vec3::new(h.x, h.y, h.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_and_inverse() {
+        let t = Transform::scale(Vec3::new(2., 1., 1.))
+            .then(&Transform::rotation(Vec3::z(), 90.))
+            .then(&Transform::translation(Vec3::new(0., 0., 5.)));
+
+        let p = t.transform_point(&Vec3::new(1., 0., 0.));
+        assert!((p - Vec3::new(0., 2., 5.)).norm() < 1e-5);
+        assert!((t.inverse_transform_point(&p) - Vec3::new(1., 0., 0.)).norm() < 1e-5);
+        assert!((t.transform_vector(&Vec3::new(1., 0., 0.)) - Vec3::new(0., 2., 0.)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn test_normal_stays_perpendicular() {
+        let t = Transform::scale(Vec3::new(4., 1., 1.)).then(&Transform::rotation(Vec3::y(), 30.));
+        let tangent = Vec3::new(1., -1., 0.);
+        let normal = Vec3::new(1., 1., 0.).normalize();
+
+        let dot = Vec3::dot(&t.transform_vector(&tangent), &t.transform_normal(&normal));
+        assert!(dot.abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_transform_aabb() {
+        let aabb = Aabb::new(Vec3::new(-1., -1., -1.), Vec3::new(1., 1., 1.));
+        let t = Transform::rotation(Vec3::z(), 45.);
+
+        let rotated = t.transform_aabb(&aabb);
+        let diagonal = 2f32.sqrt();
+        assert!((rotated.max - Vec3::new(diagonal, diagonal, 1.)).norm() < 1e-5);
+        assert!((rotated.min + Vec3::new(diagonal, diagonal, 1.)).norm() < 1e-5);
+    }
+}
@@ -2,7 +2,7 @@ use crate::Vec3;
 use crate::ray::Ray;
 use crate::aabb::Aabb;
 use crate::intersect::Intersect;
-use crate::intersection::ray_triangle_intersection;
+use crate::intersection::{ray_triangle_intersection, ray_triangle_intersection_barycentric};
 use crate::bounded::Bounded;
 
 #[derive(Copy, Clone, Debug)]
@@ -20,6 +20,12 @@ impl Triangle {
     pub fn normal(&self) -> Vec3 {
         (self.v1 - self.v0).cross(&(self.v2 - self.v0)).normalize()
     }
+
+    // t with barycentric (u, v) of hit point
+    pub fn intersect_barycentric(&self, ray: &Ray, (t_min, t_max): (f32, f32)) -> Option<(f32, f32, f32)> {
+        ray_triangle_intersection_barycentric(ray, self)
+            .filter(|(t, _, _)| t_min < *t && *t < t_max)
+    }
 }
 
 impl Intersect for Triangle {
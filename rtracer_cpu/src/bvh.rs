@@ -1,79 +1,75 @@
 use crate::prelude::*;
 use rtracer_core::prelude::*;
-use rand::Rng;
+use rtracer_core::bvh::{BvhBuilder, BvhBuildNode};
 
-pub enum BvhNodeData<H: Hit + Bounded + Copy + Clone> {
-    Leaf(H),
+pub enum BvhNodeData<H: Hit + Bounded + Clone> {
+    Leaf(Vec<H>),
     Node {
         left: Box<BvhNode<H>>,
         right: Box<BvhNode<H>>,
     },
 }
 
-pub struct BvhNode<H: Hit + Bounded + Copy + Clone> {
+pub struct BvhNode<H: Hit + Bounded + Clone> {
     data: BvhNodeData<H>,
     aabb: Aabb,
 }
 
-impl<H: Hit + Bounded + Copy + Clone> BvhNode<H> {
+impl<H: Hit + Bounded + Clone> BvhNode<H> {
     pub fn build(objs: &mut [H]) -> BvhNode<H> {
-        let sort_axis = rand::thread_rng().gen_range(0, 3);
-        objs.sort_by(|a, b| a.aabb().min[sort_axis].partial_cmp(&b.aabb().min[sort_axis]).unwrap());
-
-        let n = objs.len();
-        assert!(n != 0, "cant build bvh from zero objects");
+        BvhNode::build_with(&BvhBuilder::default(), objs)
+    }
 
-        if n == 1 {
-            BvhNode { data: BvhNodeData::Leaf(objs[0]), aabb: objs[0].aabb() }
-        } else {
-            let (mut l_objs, mut r_objs) = objs.split_at_mut(n / 2);
+    pub fn build_with(builder: &BvhBuilder, objs: &mut [H]) -> BvhNode<H> {
+        let root = builder.build(objs);
+        BvhNode::from_build_node(&root, objs)
+    }
 
-            let left = Box::new(BvhNode::build(&mut l_objs));
-            let right = Box::new(BvhNode::build(&mut r_objs));
-            let aabb = Aabb::union(&left.aabb(), &right.aabb());
+    fn from_build_node(node: &BvhBuildNode, objs: &[H]) -> BvhNode<H> {
+        match node {
+            BvhBuildNode::Leaf { aabb, objs: range } => {
+                BvhNode { data: BvhNodeData::Leaf(objs[range.clone()].to_vec()), aabb: *aabb }
+            },
+            BvhBuildNode::Node { aabb, left, right } => {
+                let left = Box::new(BvhNode::from_build_node(left, objs));
+                let right = Box::new(BvhNode::from_build_node(right, objs));
 
-            let data = BvhNodeData::Node { left, right };
-            BvhNode { data, aabb }
+                let data = BvhNodeData::Node { left, right };
+                BvhNode { data, aabb: *aabb }
+            },
         }
     }
 }
 
-impl<H: Hit + Bounded + Copy + Clone> Hit for BvhNode<H> {
-    fn hit(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<HitRecord> {
-        if self.aabb().intersect(ray, t_min_max).is_none() {
+impl<H: Hit + Bounded + Clone> Hit for BvhNode<H> {
+    fn hit(&self, ray: &Ray, (t_min, t_max): (f32, f32)) -> Option<HitRecord> {
+        if !self.aabb.overlaps_ray(ray, (t_min, t_max)) {
             return None
         }
 
         match &self.data {
-            BvhNodeData::Leaf(leaf) => {
-                leaf.hit(ray, t_min_max)
+            BvhNodeData::Leaf(leafs) => {
+                let mut closest: Option<HitRecord> = None;
+                for leaf in leafs {
+                    let t_max = closest.as_ref().map_or(t_max, |hit| hit.t);
+                    if let Some(hit) = leaf.hit(ray, (t_min, t_max)) {
+                        closest = Some(hit);
+                    }
+                }
+                closest
             },
             BvhNodeData::Node { left, right} => {
-                let l_hit = left.hit(ray, t_min_max);
-                let r_hit = right.hit(ray, t_min_max);
+                let l_hit = left.hit(ray, (t_min, t_max));
+                let t_max = l_hit.as_ref().map_or(t_max, |hit| hit.t);
+                let r_hit = right.hit(ray, (t_min, t_max));
 
-                match (l_hit, r_hit) {
-                    (Some(l_hit), Some(r_hit)) => {
-                        if l_hit.t < r_hit.t {
-                            Some(l_hit)
-                        } else {
-                            Some(r_hit)
-                        }
-                    },
-                    (Some(l_hit), None) => {
-                        Some(l_hit)
-                    },
-                    (None, Some(r_hit)) => {
-                        Some(r_hit)
-                    },
-                    _ => None
-                }
+                r_hit.or(l_hit)
             },
         }
     }
 }
 
-impl<H: Hit + Bounded + Copy + Clone> Bounded for BvhNode<H> {
+impl<H: Hit + Bounded + Clone> Bounded for BvhNode<H> {
     fn aabb(&self) -> Aabb {
         self.aabb
     }
@@ -3,14 +3,31 @@ use rtracer_core::prelude::*;
 pub struct HitRecord {
     pub t: f32,
     pub point: Vec3,
+    // shading normal, used by materials
     pub normal: Vec3,
+    pub geometric_normal: Vec3,
+    pub uv: Vec2,
+    pub vertex_color: Option<Vec3>,
     pub material: Material,
 }
 
 impl HitRecord {
     pub fn new(t: f32, point: Vec3, normal: Vec3, material: &Material) -> HitRecord {
-        debug_assert!(relative_eq!(normal.norm_squared(), 1., epsilon = std::f32::EPSILON *  4.));
-        HitRecord { t, point, normal, material: material.clone() }
+        HitRecord::with_surface(point, &SurfaceHit::new(t, normal), material)
+    }
+
+    pub fn with_surface(point: Vec3, surface: &SurfaceHit, material: &Material) -> HitRecord {
+        debug_assert!(relative_eq!(surface.shading_normal.norm_squared(), 1., epsilon = std::f32::EPSILON *  4.));
+        debug_assert!(relative_eq!(surface.normal.norm_squared(), 1., epsilon = std::f32::EPSILON *  4.));
+        HitRecord {
+            t: surface.t,
+            point,
+            normal: surface.shading_normal,
+            geometric_normal: surface.normal,
+            uv: surface.uv,
+            vertex_color: surface.color,
+            material: material.clone(),
+        }
     }
 }
 
@@ -18,21 +35,16 @@ pub trait Hit {
     fn hit(&self, ray: &Ray, t_min_max: (f32, f32)) -> Option<HitRecord>;
 }
 
+// hit points are lifted above the surface by it to avoid self intersections
+pub const SURFACE_OFFSET: f32 = 1e-2;
+
 impl Hit for Object {
     fn hit(&self, ray: &Ray, (t_min, t_max): (f32, f32)) -> Option<HitRecord> {
-        if let Some(t) = self.primitive.intersect(ray, (t_min, t_max)) {
-            let mut point = ray.point_at_parameter(t);
-
-            let normal = match &self.primitive {
-                Primitive::Sphere(s) => s.normal_at(&point),
-                Primitive::Plane(s) => s.normal,
-                Primitive::Cube(s) => s.normal_at(&point),
-                Primitive::Triangle(s) => s.normal(),
-                Primitive::Disk(s) => s.plane.normal,
-            };
+        if let Some(hit) = self.primitive.hit_surface(ray, (t_min, t_max)) {
+            let mut point = ray.point_at_parameter(hit.t);
 
-            point += normal * 1e-2;
-            Some(HitRecord::new(t, point, normal, &self.material))
+            point += hit.normal * SURFACE_OFFSET;
+            Some(HitRecord::with_surface(point, &hit, &self.material))
         } else {
             None
         }
@@ -13,6 +13,10 @@ impl<H: Hit> HitableList<H> {
     pub fn add(&mut self, obj: H) {
         self.hitable.push(obj)
     }
+
+    pub fn iter(&self) -> impl Iterator<Item=&H> {
+        self.hitable.iter()
+    }
 }
 
 impl<H: Hit + Bounded> Default for HitableList<H> {
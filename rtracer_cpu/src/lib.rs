@@ -12,3 +12,6 @@ mod hitable_list;
 mod scatter;
 mod renderer_cpu;
 mod bvh;
+mod sampler;
+mod microfacet;
+mod light;
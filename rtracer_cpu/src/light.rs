@@ -0,0 +1,303 @@
+use std::f32::consts::PI;
+
+use rand::Rng;
+
+use rtracer_core::prelude::*;
+
+use crate::sampler::{local_to_world, orthonormal_basis, uniform_cone, uniform_in_disk};
+
+// relative difference of distances to the same hit
+const SAME_HIT_EPSILON: f32 = 1e-3;
+
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f32,
+    pub radiance: Vec3,
+    // with respect to solid angle
+    pub pdf: f32,
+}
+
+#[derive(Copy, Clone)]
+enum LightShape {
+    Sphere(Sphere),
+    Triangle(Triangle),
+    Disk(Disk),
+}
+
+#[derive(Copy, Clone)]
+pub struct Light {
+    shape: LightShape,
+    radiance: Vec3,
+}
+
+impl Light {
+    // only emissive static spheres, triangles and disks can be sampled directly
+    pub fn from_object(object: &Object) -> Option<Light> {
+        let radiance = match &object.material {
+            Material::Emissive(e) => e.radiance,
+            _ => return None,
+        };
+
+        let shape = match &object.primitive {
+            Primitive::Sphere(s) if !s.is_moving() => LightShape::Sphere(Sphere::new(s.center, s.radius.abs())),
+            Primitive::Triangle(t) => LightShape::Triangle(*t),
+            Primitive::Disk(d) => LightShape::Disk(*d),
+            _ => return None,
+        };
+
+        Some(Light { shape, radiance })
+    }
+
+    pub fn sample<R: Rng>(&self, point: &Vec3, rng: &mut R) -> Option<LightSample> {
+        match &self.shape {
+            LightShape::Sphere(s) => {
+                let to_center = s.center - *point;
+                let dist2 = to_center.norm_squared();
+                if dist2 <= s.radius * s.radius {
+                    return None
+                }
+
+                let cos_max = (1. - s.radius * s.radius / dist2).max(0.).sqrt();
+                let direction = local_to_world(&uniform_cone(cos_max, rng), &to_center.normalize()).normalize();
+
+                let distance = s.intersect(&Ray::new(*point, direction), (0., std::f32::MAX))
+                    .unwrap_or_else(|| dist2.sqrt());
+
+                Some(LightSample { direction, distance, radiance: self.radiance, pdf: cone_pdf(cos_max) })
+            },
+            LightShape::Triangle(t) => {
+                let (u, v) = (rng.gen::<f32>().sqrt(), rng.gen::<f32>());
+                let light_point = (1. - u) * t.v0 + u * (1. - v) * t.v1 + u * v * t.v2;
+                let area = 0.5 * (t.v1 - t.v0).cross(&(t.v2 - t.v0)).norm();
+
+                self.area_sample(point, &light_point, &t.normal(), area)
+            },
+            LightShape::Disk(d) => {
+                let (t, b) = orthonormal_basis(&d.plane.normal);
+                let (x, y) = uniform_in_disk(rng);
+                let light_point = d.plane.origin + d.radius * (x * t + y * b);
+
+                self.area_sample(point, &light_point, &d.plane.normal, PI * d.radius * d.radius)
+            },
+        }
+    }
+
+    // solid angle pdf of sample() returning ray direction,
+    // zero if light is not hit at distance, lights behind the hit one could not be sampled
+    pub fn pdf(&self, ray: &Ray, distance: f32) -> f32 {
+        let at_distance = |dist: &f32| (dist - distance).abs() <= SAME_HIT_EPSILON * distance.max(1.);
+        match &self.shape {
+            LightShape::Sphere(s) => {
+                let dist2 = (s.center - ray.origin).norm_squared();
+                if dist2 <= s.radius * s.radius || s.intersect(ray, (0., std::f32::MAX)).filter(at_distance).is_none() {
+                    return 0.
+                }
+
+                cone_pdf((1. - s.radius * s.radius / dist2).max(0.).sqrt())
+            },
+            LightShape::Triangle(t) => {
+                let area = 0.5 * (t.v1 - t.v0).cross(&(t.v2 - t.v0)).norm();
+                t.intersect(ray, (0., std::f32::MAX)).filter(at_distance)
+                    .map_or(0., |dist| area_pdf(ray, dist, &t.normal(), area))
+            },
+            LightShape::Disk(d) => {
+                d.intersect(ray, (0., std::f32::MAX)).filter(at_distance)
+                    .map_or(0., |dist| area_pdf(ray, dist, &d.plane.normal, PI * d.radius * d.radius))
+            },
+        }
+    }
+
+    fn area_sample(&self, point: &Vec3, light_point: &Vec3, normal: &Vec3, area: f32) -> Option<LightSample> {
+        let to_light = *light_point - *point;
+        let distance = to_light.norm();
+        if distance <= 0. {
+            return None
+        }
+
+        let direction = to_light / distance;
+        let cosin = Vec3::dot(normal, &direction).abs();
+        if cosin <= 0. {
+            return None
+        }
+
+        let pdf = distance * distance / (cosin * area);
+        Some(LightSample { direction, distance, radiance: self.radiance, pdf })
+    }
+}
+
+fn cone_pdf(cos_max: f32) -> f32 {
+    1. / (2. * PI * (1. - cos_max))
+}
+
+fn area_pdf(ray: &Ray, dist: f32, normal: &Vec3, area: f32) -> f32 {
+    let cosin = Vec3::dot(normal, &ray.direction).abs();
+    if cosin <= 0. {
+        0.
+    } else {
+        dist * dist / (cosin * area)
+    }
+}
+
+pub struct LightList {
+    lights: Vec<Light>,
+    // sampled as one more light, rays missing the scene hit it
+    background: Option<Background>,
+}
+
+impl LightList {
+    pub fn new() -> LightList {
+        LightList { lights: Vec::new(), background: None }
+    }
+
+    pub fn from_objects<'a>(objs: impl IntoIterator<Item=&'a Object>) -> LightList {
+        LightList { lights: objs.into_iter().filter_map(Light::from_object).collect(), background: None }
+    }
+
+    pub fn add(&mut self, light: Light) {
+        self.lights.push(light)
+    }
+
+    pub fn set_background(&mut self, background: &Background) {
+        self.background = Some(background.clone()).filter(Background::is_light);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len() + self.background.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // picks one light uniformly
+    pub fn sample<R: Rng>(&self, point: &Vec3, rng: &mut R) -> Option<LightSample> {
+        if self.is_empty() {
+            return None
+        }
+
+        let idx = rng.gen_range(0, self.len());
+        let sample = match self.lights.get(idx) {
+            Some(light) => light.sample(point, rng),
+            None => self.background.as_ref().and_then(|background| background_sample(background, rng)),
+        };
+
+        sample.map(|mut sample| {
+            sample.pdf /= self.len() as f32;
+            sample
+        })
+    }
+
+    // pdf of sampling ray that hits emissive object at distance
+    pub fn pdf(&self, ray: &Ray, distance: f32) -> f32 {
+        if self.is_empty() {
+            return 0.
+        }
+
+        let sum: f32 = self.lights.iter().map(|l| l.pdf(ray, distance)).sum();
+        sum / self.len() as f32
+    }
+
+    // pdf of sampling ray that leaves the scene
+    pub fn environment_pdf(&self, direction: &Vec3) -> f32 {
+        match &self.background {
+            Some(background) => background.pdf(direction) / self.len() as f32,
+            None => 0.,
+        }
+    }
+}
+
+fn background_sample<R: Rng>(background: &Background, rng: &mut R) -> Option<LightSample> {
+    let (direction, pdf) = background.sample((rng.gen(), rng.gen()))?;
+    Some(LightSample { direction, distance: std::f32::MAX, radiance: background.radiance(&direction), pdf })
+}
+
+impl Default for LightList {
+    fn default() -> LightList {
+        LightList::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::sample_rng;
+    use rtracer_core::image::Image;
+
+    fn check_sample_pdf(object: Object) {
+        let light = Light::from_object(&object).unwrap();
+        let point = Vec3::new(0.3, -2., 0.5);
+        let mut rng = sample_rng(0, 0, 0);
+
+        for _ in 0..32 {
+            let sample = light.sample(&point, &mut rng).unwrap();
+            let pdf = light.pdf(&Ray::new(point, sample.direction), sample.distance);
+            assert!(relative_eq!(sample.pdf, pdf, max_relative = 1e-3), "{} != {}", sample.pdf, pdf);
+        }
+    }
+
+    #[test]
+    fn test_sphere_sample_pdf() {
+        let emissive = Material::Emissive(Emissive::new(Vec3::repeat(1.)));
+        check_sample_pdf(Object::new_sphere(Sphere::new(Vec3::new(0., 1., 0.), 0.5), emissive));
+    }
+
+    #[test]
+    fn test_triangle_sample_pdf() {
+        let emissive = Material::Emissive(Emissive::new(Vec3::repeat(1.)));
+        let triangle = Triangle::new(Vec3::new(-1., 1., -1.), Vec3::new(1., 1., -1.), Vec3::new(0., 1., 1.));
+        check_sample_pdf(Object::new_triangle(triangle, emissive));
+    }
+
+    #[test]
+    fn test_disk_sample_pdf() {
+        let emissive = Material::Emissive(Emissive::new(Vec3::repeat(1.)));
+        let disk = Disk::new(Plane::new(Vec3::new(0., 1., 0.), -Vec3::y()), 0.7);
+        check_sample_pdf(Object::new_disk(disk, emissive));
+    }
+
+    #[test]
+    fn test_stacked_lights_pdf() {
+        let emissive = Material::Emissive(Emissive::new(Vec3::repeat(1.)));
+        let near = Object::new_disk(Disk::new(Plane::new(Vec3::new(0., 1., 0.), -Vec3::y()), 0.5), emissive.clone());
+        let far = Object::new_disk(Disk::new(Plane::new(Vec3::new(0., 2., 0.), -Vec3::y()), 0.5), emissive);
+        let lights = LightList::from_objects(&[near.clone(), far]);
+
+        // only the near light is hit, the far one behind it is not counted
+        let ray = Ray::new(Vec3::zeros(), Vec3::y());
+        let near_pdf = Light::from_object(&near).unwrap().pdf(&ray, 1.);
+        assert!(near_pdf > 0.);
+        assert!(relative_eq!(lights.pdf(&ray, 1.), near_pdf / 2., max_relative = 1e-5));
+        assert_eq!(lights.pdf(&ray, 1.5), 0.);
+    }
+
+    #[test]
+    fn test_environment_sample_pdf() {
+        let mut image = Image::with_background(8, 4, Vec3::repeat(0.2));
+        image[(2, 1)] = Vec3::repeat(20.);
+        let background = Background::from(EnvironmentMap::new(image, 45., 1.));
+
+        let emissive = Material::Emissive(Emissive::new(Vec3::repeat(1.)));
+        let mut lights = LightList::from_objects(&[Object::new_sphere(Sphere::new(Vec3::new(0., 5., 0.), 0.5), emissive)]);
+        lights.set_background(&background);
+        assert_eq!(lights.len(), 2);
+
+        let mut rng = sample_rng(0, 0, 0);
+        let mut environment_samples = 0;
+        for _ in 0..64 {
+            let sample = lights.sample(&Vec3::zeros(), &mut rng).unwrap();
+            if sample.distance == std::f32::MAX {
+                let pdf = lights.environment_pdf(&sample.direction);
+                assert!(relative_eq!(sample.pdf, pdf, max_relative = 1e-3), "{} != {}", sample.pdf, pdf);
+                assert_eq!(sample.radiance, background.radiance(&sample.direction));
+                environment_samples += 1;
+            }
+        }
+        assert!(environment_samples > 16 && environment_samples < 48);
+    }
+
+    #[test]
+    fn test_not_emissive_is_not_light() {
+        let lambertian = Material::Lambertian(Lambertian::new(Vec3::repeat(0.5)));
+        assert!(Light::from_object(&Object::new_sphere(Sphere::new(Vec3::zeros(), 1.), lambertian)).is_none());
+    }
+}
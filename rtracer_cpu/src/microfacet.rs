@@ -0,0 +1,182 @@
+use std::f32::consts::PI;
+
+use rtracer_core::prelude::*;
+
+// all directions are in local frame of the surface, normal is z
+
+// roughness is squared to get perceptually linear highlights
+pub fn roughness_to_alpha(roughness: f32) -> f32 {
+    (roughness * roughness).max(1e-3)
+}
+
+// ggx (trowbridge-reitz) distribution of microfacet normals
+pub fn ggx_d(h: &Vec3, alpha: f32) -> f32 {
+    if h.z <= 0. {
+        return 0.
+    }
+    let a2 = alpha * alpha;
+    let d = h.z * h.z * (a2 - 1.) + 1.;
+    a2 / (PI * d * d)
+}
+
+fn smith_lambda(v: &Vec3, alpha: f32) -> f32 {
+    let cos2 = v.z * v.z;
+    if cos2 == 0. {
+        return std::f32::INFINITY
+    }
+    let tan2 = (1. - cos2).max(0.) / cos2;
+    0.5 * ((1. + alpha * alpha * tan2).sqrt() - 1.)
+}
+
+// smith masking of one direction
+pub fn smith_g1(v: &Vec3, alpha: f32) -> f32 {
+    1. / (1. + smith_lambda(v, alpha))
+}
+
+// height correlated masking-shadowing
+pub fn smith_g2(wo: &Vec3, wi: &Vec3, alpha: f32) -> f32 {
+    1. / (1. + smith_lambda(wo, alpha) + smith_lambda(wi, alpha))
+}
+
+// microfacet normal from distribution of normals visible from wo, Heitz 2018
+pub fn sample_vndf(wo: &Vec3, alpha: f32, (u1, u2): (f32, f32)) -> Vec3 {
+    let vh = Vec3::new(alpha * wo.x, alpha * wo.y, wo.z).normalize();
+
+    let len2 = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if len2 > 0. { Vec3::new(-vh.y, vh.x, 0.) / len2.sqrt() } else { Vec3::x() };
+    let t2 = vh.cross(&t1);
+
+    let r = u1.sqrt();
+    let phi = 2. * PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1. + vh.z);
+    let p2 = (1. - s) * (1. - p1 * p1).max(0.).sqrt() + s * r * phi.sin();
+
+    let nh = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * vh;
+    Vec3::new(alpha * nh.x, alpha * nh.y, nh.z.max(0.)).normalize()
+}
+
+// pdf of wi reflected around normal sampled by sample_vndf
+pub fn vndf_reflection_pdf(wo: &Vec3, h: &Vec3, alpha: f32) -> f32 {
+    if wo.z <= 0. {
+        return 0.
+    }
+    smith_g1(wo, alpha) * ggx_d(h, alpha) / (4. * wo.z)
+}
+
+pub fn fresnel_schlick(cos: f32, f0: &Vec3) -> Vec3 {
+    let m = (1. - cos).max(0.).min(1.).powi(5);
+    f0 + (Vec3::repeat(1.) - f0) * m
+}
+
+// unpolarized reflectance, eta is ratio of inner to outer ior
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_i.max(0.).min(1.);
+    let sin2_t = (1. - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1. {
+        return 1.
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+// unpolarized reflectance of metal with complex ior eta + ik
+pub fn fresnel_conductor(cos_i: f32, eta: &Vec3, k: &Vec3) -> Vec3 {
+    let cos_i = cos_i.max(0.).min(1.);
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1. - cos2;
+
+    let channel = |eta: f32, k: f32| {
+        let t0 = eta * eta - k * k - sin2;
+        let a2b2 = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+        let a = (0.5 * (a2b2 + t0)).max(0.).sqrt();
+        let t1 = a2b2 + cos2;
+        let t2 = 2. * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rs + rp)
+    };
+
+    Vec3::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use crate::sampler::sample_rng;
+
+    fn uniform_hemisphere(u1: f32, u2: f32) -> Vec3 {
+        let z = u1;
+        let r = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * PI * u2;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    #[test]
+    fn test_ggx_normalized() {
+        let mut rng = sample_rng(1, 0, 0);
+        for &alpha in &[0.2, 0.5, 1.] {
+            // projected area of microfacets equals macro surface
+            let n = 50_000;
+            let sum: f32 = (0..n)
+                .map(|_| uniform_hemisphere(rng.gen(), rng.gen()))
+                .map(|h| ggx_d(&h, alpha) * h.z * 2. * PI)
+                .sum();
+            assert!(relative_eq!(sum / n as f32, 1., epsilon = 0.03), "{} {}", alpha, sum / n as f32);
+        }
+    }
+
+    #[test]
+    fn test_vndf_pdf_matches_sampling() {
+        let mut rng = sample_rng(2, 0, 0);
+        let wo = Vec3::new(0.6, 0., 0.8);
+        for &alpha in &[0.3, 0.8] {
+            let n = 50_000;
+            let integral: f32 = (0..n)
+                .map(|_| uniform_hemisphere(rng.gen(), rng.gen()))
+                .map(|wi| vndf_reflection_pdf(&wo, &(wo + wi).normalize(), alpha) * 2. * PI)
+                .sum::<f32>() / n as f32;
+
+            // reflections going below the surface are lost by both
+            let above = (0..n)
+                .map(|_| sample_vndf(&wo, alpha, (rng.gen(), rng.gen())))
+                .filter(|h| (2. * Vec3::dot(&wo, h) * h - wo).z > 0.)
+                .count() as f32 / n as f32;
+
+            assert!(relative_eq!(integral, above, epsilon = 0.02), "{} {} {}", alpha, integral, above);
+        }
+    }
+
+    #[test]
+    fn test_vndf_samples_visible_normals() {
+        let mut rng = sample_rng(3, 0, 0);
+        let wo = Vec3::new(0.8, 0., 0.6);
+        for _ in 0..1000 {
+            let h = sample_vndf(&wo, 0.5, (rng.gen(), rng.gen()));
+            assert!(relative_eq!(h.norm(), 1., epsilon = 1e-4));
+            assert!(h.z >= 0. && Vec3::dot(&wo, &h) >= -1e-4);
+        }
+    }
+
+    #[test]
+    fn test_fresnel() {
+        assert!(relative_eq!(fresnel_dielectric(1., 1.5), 0.04, epsilon = 1e-4));
+        assert!(relative_eq!(fresnel_dielectric(0., 1.5), 1., epsilon = 1e-4));
+        // total internal reflection from inside
+        assert_eq!(fresnel_dielectric(0.3, 1. / 1.5), 1.);
+
+        // k = 0 conductor is a dielectric
+        let f = fresnel_conductor(0.7, &Vec3::repeat(1.5), &Vec3::zeros());
+        assert!(relative_eq!(f.x, fresnel_dielectric(0.7, 1.5), epsilon = 1e-4));
+        // gold is more reflective in red
+        let gold = fresnel_conductor(1., &Vec3::new(0.143, 0.374, 1.442), &Vec3::new(3.983, 2.385, 1.603));
+        assert!(gold.x > 0.9 && gold.z < 0.5);
+
+        assert_eq!(fresnel_schlick(1., &Vec3::repeat(0.5)), Vec3::repeat(0.5));
+    }
+}
@@ -2,6 +2,8 @@ pub use crate::{
     hit::{HitRecord, Hit},
     hitable_list::HitableList,
     scatter::{Scatter, ScatteredRay},
-    renderer_cpu::CPURenderer,
+    renderer_cpu::{CPURenderer, autofocus},
     bvh::BvhNode,
+    sampler::{SampleRng, sample_rng},
+    light::{Light, LightList, LightSample},
 };
\ No newline at end of file
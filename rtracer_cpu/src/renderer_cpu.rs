@@ -2,77 +2,175 @@ extern crate rayon;
 
 use rayon::prelude::*;
 
-use itertools::iproduct;
+use rand::Rng;
 
-use crate::hitable_list::HitableList;
-use crate::hit::Hit;
-use rtracer_core::image::{Image, ColorRGB, gamma_correction};
-use rtracer_core::prelude::{Vec3, Ray, Camera, RaycastCamera};
+use crate::hit::{Hit, HitRecord};
+use rtracer_core::image::{Image, ColorRGB};
+use rtracer_core::prelude::{Vec3, Ray, Camera, RaycastCamera, Background};
 use crate::scatter::Scatter;
+use crate::sampler::{sample_rng, power_heuristic, uniform_in_disk};
+use crate::light::LightList;
+
+const SHADOW_EPSILON: f32 = 1e-3;
 
 pub struct CPURenderer {
     rays_for_pixel: u32,
     max_ray_depth: u32,
+    seed: u64,
+    background: Background,
 }
 
 impl CPURenderer {
     pub fn new(rays_for_pixel: u32, max_ray_depth: u32) -> CPURenderer {
-        CPURenderer { rays_for_pixel, max_ray_depth }
+        CPURenderer { rays_for_pixel, max_ray_depth, seed: 0, background: Background::default() }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
     }
 
-    pub fn render<H: Hit + Sync + Send>(&self, image: &mut Image, camera: &Camera, scene: &H) {
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
+
+    // environment map or sky should also be set to light list to be sampled
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    // image gets linear radiance, use Film to get displayable colors
+    pub fn render<H: Hit + Sync + Send>(&self, image: &mut Image, camera: &Camera, scene: &H, lights: &LightList) {
         let (width, height) = (image.width(), image.height());
 
         let raycast_camera = RaycastCamera::from_camera(&camera);
 
-        iproduct!((0..height), (0..width))
-            .zip(image.buf_mut().iter_mut())
-            .par_bridge()
-            .for_each(|((y, x), pixel)| {
+        image.buf_mut()
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(idx, pixel)| {
+                let (x, y) = (idx as u32 % width, idx as u32 / width);
                 let mut total_color = ColorRGB::zeros();
 
-                for _ in 0..self.rays_for_pixel {
-                    let (u, v) = ((x as f32 + rand::random::<f32>()) / width as f32,
-                                  (y as f32 + rand::random::<f32>()) / height as f32);
-                    let ray = raycast_camera.get_ray((u, v));
+                for sample in 0..self.rays_for_pixel {
+                    let mut rng = sample_rng(self.seed, idx as u64, sample as u64);
 
-                    total_color += self.color(&ray, 0, scene);
+                    let (u, v) = ((x as f32 + rng.gen::<f32>()) / width as f32,
+                                  (y as f32 + rng.gen::<f32>()) / height as f32);
+                    // points outside of fisheye circle stay black
+                    if let Some(ray) = raycast_camera.get_ray((u, v), uniform_in_disk(&mut rng), raycast_camera.shutter_time(rng.gen())) {
+                        total_color += self.color(&ray, 0, scene, lights, &mut rng, None);
+                    }
                 }
                 total_color /= self.rays_for_pixel as f32;
-                total_color = gamma_correction(&total_color, 2f32);
 
                 *pixel = total_color;
             });
     }
 
-    fn color<H: Hit>(&self, ray: &Ray, depth: u32, scene: &H) -> ColorRGB {
+    // bsdf_pdf - pdf of sampling ray at previous hit, None for camera rays and specular bounces
+    fn color<H: Hit, R: Rng>(&self, ray: &Ray, depth: u32, scene: &H, lights: &LightList, rng: &mut R,
+                             bsdf_pdf: Option<f32>) -> ColorRGB {
         if let Some(rec) = scene.hit(ray, (0., std::f32::MAX)) {
-            if let Some(scattered) = rec.material.scatter(ray, &rec) {
-                if depth < self.max_ray_depth {
-                    let attenuation: Vec3 = scattered.attenuation;
-                    let color: Vec3 = self.color(&scattered.ray, depth + 1, scene);
-
-                    let res = Vec3::new(
-                        attenuation.x * color.x,
-                        attenuation.y * color.y,
-                        attenuation.z * color.z,
-                    );
-
-                    return res;
-                    // !todo:
-//                    return scattered.attenuation * self.color(&scattered.ray, depth + 1, scene);
+            let mut color = rec.material.emitted(ray, &rec);
+
+            // this light was also reachable by light sampling at previous hit
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if color != ColorRGB::zeros() {
+                    color *= power_heuristic(bsdf_pdf, lights.pdf(ray, rec.t));
                 }
             }
-            Vec3::zeros()
+
+            if depth < self.max_ray_depth {
+                color += self.sample_lights(ray, &rec, scene, lights, rng);
+
+                if let Some(scattered) = rec.material.scatter(ray, &rec, rng) {
+                    let incoming = self.color(&scattered.ray, depth + 1, scene, lights, rng, scattered.pdf);
+                    color += scattered.attenuation.component_mul(&incoming);
+                }
+            }
+
+            color
         } else {
-//            let unit_direction = ray.direction.make_unit();
-//            let t = 0.5f32 * (unit_direction.y() + 1f32);
-//            (1f32 - t) * Vec3::new(1f32, 1f32, 1f32) + t * Vec3::new(0.5f32, 0.7f32, 1f32)
-            Vec3::identity()
+            let mut color = self.background.radiance(&ray.direction);
+
+            // environment is sampled by light list too
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                color *= power_heuristic(bsdf_pdf, lights.environment_pdf(&ray.direction));
+            }
+
+            color
+        }
+    }
+
+    fn sample_lights<H: Hit, R: Rng>(&self, ray: &Ray, rec: &HitRecord, scene: &H, lights: &LightList, rng: &mut R) -> ColorRGB {
+        if let Some(sample) = lights.sample(&rec.point, rng) {
+            if let Some((bsdf, bsdf_pdf)) = rec.material.eval(ray, rec, &sample.direction) {
+                if bsdf == ColorRGB::zeros() {
+                    return ColorRGB::zeros()
+                }
+
+                let shadow_ray = Ray::with_time(rec.point, sample.direction, ray.time);
+                if scene.hit(&shadow_ray, (0., sample.distance * (1. - SHADOW_EPSILON))).is_none() {
+                    let weight = power_heuristic(sample.pdf, bsdf_pdf);
+                    return bsdf.component_mul(&sample.radiance) * weight / sample.pdf;
+                }
+            }
         }
+
+        ColorRGB::zeros()
     }
 }
 
+// moves focus plane to the first surface seen in the image center, returns new focus distance
+pub fn autofocus<H: Hit>(camera: &mut Camera, scene: &H) -> Option<f32> {
+    let ray = camera.center_ray();
+    let rec = scene.hit(&ray, (0., std::f32::MAX))?;
+
+    // focus distance is measured along view direction, center ray goes along it
+    camera.focus_distance = rec.t;
+    Some(rec.t)
+}
+
 fn normal_to_color(normal: &Vec3) -> Vec3 {
     (Vec3::new(1., 1., 1.) + *normal) * 0.5
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtracer_core::prelude::*;
+    use crate::hitable_list::HitableList;
+
+    #[test]
+    fn test_emitter_radiance() {
+        let mut scene = HitableList::new();
+        let radiance = Vec3::new(4., 2., 1.);
+        scene.add(Object::new_sphere(Sphere::new(Vec3::new(0., 0., -5.), 1.), Material::Emissive(Emissive::new(radiance))));
+        let lights = LightList::from_objects(scene.iter());
+
+        // without bounces camera ray sees emitted radiance only
+        let renderer = CPURenderer::new(1, 0);
+        let mut rng = sample_rng(0, 0, 0);
+        let color = renderer.color(&Ray::new(Vec3::zeros(), -Vec3::z()), 0, &scene, &lights, &mut rng, None);
+        assert_eq!(color, radiance);
+    }
+
+    #[test]
+    fn test_autofocus() {
+        let mut scene = HitableList::new();
+        let material = Material::Lambertian(Lambertian::new(Vec3::repeat(0.5)));
+        scene.add(Object::new_sphere(Sphere::new(Vec3::new(0., 0., -5.), 1.), material));
+
+        let mut camera = Camera::new(Vec3::zeros(), -Vec3::z(), Vec3::y(), 40., 1.).with_lens(0.2, 1.);
+        assert!(relative_eq!(autofocus(&mut camera, &scene).unwrap(), 4., epsilon = 1e-4));
+        assert!(relative_eq!(camera.focus_distance, 4., epsilon = 1e-4));
+
+        // nothing behind the camera, focus is kept
+        let mut camera = Camera::new(Vec3::zeros(), Vec3::z(), Vec3::y(), 40., 1.).with_lens(0.2, 1.);
+        assert!(autofocus(&mut camera, &scene).is_none());
+        assert_eq!(camera.focus_distance, 1.);
+    }
+}
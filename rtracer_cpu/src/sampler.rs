@@ -0,0 +1,99 @@
+use std::f32::consts::PI;
+
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+use rtracer_core::prelude::*;
+
+pub type SampleRng = Pcg32;
+
+// every (pixel, sample) pair gets its own generator, so image does not depend
+// on the order pixels are rendered in
+pub fn sample_rng(seed: u64, pixel: u64, sample: u64) -> SampleRng {
+    let state = splitmix64(seed ^ splitmix64(pixel ^ splitmix64(sample)));
+    SampleRng::seed_from_u64(state)
+}
+
+// any two unit vectors orthogonal to n and each other
+pub fn orthonormal_basis(n: &Vec3) -> (Vec3, Vec3) {
+    let a = if n.x.abs() > 0.9 { Vec3::y() } else { Vec3::x() };
+    let t = n.cross(&a).normalize();
+    let b = n.cross(&t);
+    (t, b)
+}
+
+pub fn local_to_world(local: &Vec3, n: &Vec3) -> Vec3 {
+    let (t, b) = orthonormal_basis(n);
+    local.x * t + local.y * b + local.z * *n
+}
+
+pub fn uniform_in_disk<R: Rng>(rng: &mut R) -> (f32, f32) {
+    let r = rng.gen::<f32>().sqrt();
+    let phi = 2. * PI * rng.gen::<f32>();
+    (r * phi.cos(), r * phi.sin())
+}
+
+// pdf is cos_theta / PI
+pub fn cosine_hemisphere<R: Rng>(rng: &mut R) -> Vec3 {
+    let (x, y) = uniform_in_disk(rng);
+    Vec3::new(x, y, (1. - x * x - y * y).max(0.).sqrt())
+}
+
+// uniform direction inside cone around z axis, pdf is 1 / (2 * PI * (1 - cos_max))
+pub fn uniform_cone<R: Rng>(cos_max: f32, rng: &mut R) -> Vec3 {
+    let cos_theta = 1. - rng.gen::<f32>() * (1. - cos_max);
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let phi = 2. * PI * rng.gen::<f32>();
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+pub fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0. {
+        0.
+    } else {
+        a / (a + b)
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = sample_rng(42, 7, 3);
+        let mut b = sample_rng(42, 7, 3);
+        for _ in 0..16 {
+            assert_eq!(a.gen::<u32>(), b.gen::<u32>());
+        }
+    }
+
+    #[test]
+    fn test_orthonormal_basis() {
+        for n in &[Vec3::x(), -Vec3::y(), Vec3::new(1., 2., -3.).normalize()] {
+            let (t, b) = orthonormal_basis(n);
+            assert!(relative_eq!(t.norm(), 1., epsilon = 1e-5));
+            assert!(relative_eq!(b.norm(), 1., epsilon = 1e-5));
+            assert!(Vec3::dot(&t, n).abs() < 1e-5);
+            assert!(Vec3::dot(&b, n).abs() < 1e-5);
+            assert!(Vec3::dot(&t, &b).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_different_samples_differ() {
+        let a = sample_rng(42, 7, 3).gen::<u64>();
+        assert_ne!(a, sample_rng(42, 7, 4).gen::<u64>());
+        assert_ne!(a, sample_rng(42, 8, 3).gen::<u64>());
+        assert_ne!(a, sample_rng(43, 7, 3).gen::<u64>());
+    }
+}
@@ -1,54 +1,99 @@
+use std::f32::consts::PI;
+
 use rand::distributions::{UnitSphereSurface, Distribution};
 use rand::Rng;
 
 use rtracer_core::prelude::*;
 
 use crate::prelude::*;
+use crate::hit::SURFACE_OFFSET;
+use crate::sampler::{orthonormal_basis, cosine_hemisphere};
+use crate::microfacet::*;
 
 pub struct ScatteredRay {
     pub ray: Ray,
     pub attenuation: Vec3,
+    // None for specular scattering, such directions cant be reached by light sampling
+    pub pdf: Option<f32>,
 }
 
 impl ScatteredRay {
     pub fn new(ray: Ray, attenuation: Vec3) -> ScatteredRay {
-        ScatteredRay { ray, attenuation }
+        ScatteredRay { ray, attenuation, pdf: None }
+    }
+
+    pub fn with_pdf(ray: Ray, attenuation: Vec3, pdf: f32) -> ScatteredRay {
+        ScatteredRay { ray, attenuation, pdf: Some(pdf) }
     }
 }
 
 pub trait Scatter: Send + Sync {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay>;
+    fn scatter<R: Rng>(&self, ray: &Ray, hit: &HitRecord, rng: &mut R) -> Option<ScatteredRay>;
+
+    fn emitted(&self, _ray: &Ray, _hit: &HitRecord) -> Vec3 {
+        Vec3::zeros()
+    }
+
+    // brdf * cos and pdf of scattering into direction, None for specular materials
+    fn eval(&self, _ray: &Ray, _hit: &HitRecord, _direction: &Vec3) -> Option<(Vec3, f32)> {
+        None
+    }
 }
 
 impl Scatter for Material {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay> {
+    fn scatter<R: Rng>(&self, ray: &Ray, hit: &HitRecord, rng: &mut R) -> Option<ScatteredRay> {
+        match self {
+            Material::Lambertian(m) => m.scatter(ray, hit, rng),
+            Material::Metal(m) => m.scatter(ray, hit, rng),
+            Material::Dielectric(m) => m.scatter(ray, hit, rng),
+            Material::Emissive(m) => m.scatter(ray, hit, rng),
+            Material::Microfacet(m) => m.scatter(ray, hit, rng),
+        }
+    }
+
+    fn emitted(&self, ray: &Ray, hit: &HitRecord) -> Vec3 {
+        match self {
+            Material::Emissive(m) => m.emitted(ray, hit),
+            _ => Vec3::zeros(),
+        }
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Option<(Vec3, f32)> {
         match self {
-            Material::Lambertian(m) => m.scatter(ray, hit),
-            Material::Metal(m) => m.scatter(ray, hit),
-            Material::Dielectric(m) => m.scatter(ray, hit),
+            Material::Lambertian(m) => m.eval(ray, hit, direction),
+            Material::Microfacet(m) => m.eval(ray, hit, direction),
+            _ => None,
         }
     }
 }
 
 impl Scatter for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay> {
-        let target = hit.normal + random_in_unit_sphere();
-        Some(ScatteredRay::new(Ray::new(hit.point, target), self.albedo))
+    fn scatter<R: Rng>(&self, ray: &Ray, hit: &HitRecord, rng: &mut R) -> Option<ScatteredRay> {
+        let target = hit.normal + random_in_unit_sphere(rng);
+        let scattered = Ray::with_time(hit.point, target, ray.time);
+
+        let pdf = Vec3::dot(&scattered.direction, &hit.normal).max(0.) / PI;
+        Some(ScatteredRay::with_pdf(scattered, albedo(&self.albedo, hit), pdf))
+    }
+
+    fn eval(&self, _ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Option<(Vec3, f32)> {
+        let cosin = Vec3::dot(direction, &hit.normal).max(0.);
+        Some((albedo(&self.albedo, hit) * cosin / PI, cosin / PI))
     }
 }
 
 impl Scatter for Metal {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay> {
+    fn scatter<R: Rng>(&self, ray: &Ray, hit: &HitRecord, rng: &mut R) -> Option<ScatteredRay> {
         let reflected = reflect(&ray.direction, &hit.normal);
         if Vec3::dot(&reflected, &hit.normal) > 0f32 {
-            return Some(ScatteredRay::new(Ray::new(hit.point, reflected + self.fuzz * random_in_unit_sphere()), self.albedo));
+            return Some(ScatteredRay::new(Ray::with_time(hit.point, reflected + self.fuzz * random_in_unit_sphere(rng), ray.time), albedo(&self.albedo, hit)));
         }
         None
     }
 }
 
 impl Scatter for Dielectric {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay> {
+    fn scatter<R: Rng>(&self, ray: &Ray, hit: &HitRecord, rng: &mut R) -> Option<ScatteredRay> {
         let (outward_normal, ni_over_nt, cosin) = if Vec3::dot(&ray.direction, &hit.normal) > 0. {
             (-hit.normal, self.ref_idx, self.ref_idx * Vec3::dot(&ray.direction, &hit.normal) / ray.direction.norm())
         } else {
@@ -59,7 +104,7 @@ impl Scatter for Dielectric {
 
         let dir = if let Some(refracted) = refract(&ray.direction, &outward_normal, ni_over_nt) {
             let reflect_prob = schlick(cosin, self.ref_idx);
-            if rand::thread_rng().gen::<f32>() < reflect_prob {
+            if rng.gen::<f32>() < reflect_prob {
                 reflected
             } else {
                 refracted
@@ -68,13 +113,125 @@ impl Scatter for Dielectric {
             reflected
         };
 
-        Some(ScatteredRay::new(Ray::new(hit.point, dir), self.attenuation))
+        // refracted rays and internal reflections start below the lifted hit point
+        let origin = if Vec3::dot(&dir, &hit.geometric_normal) < 0. {
+            hit.point - 2. * SURFACE_OFFSET * hit.geometric_normal
+        } else {
+            hit.point
+        };
+
+        Some(ScatteredRay::new(Ray::with_time(origin, dir, ray.time), self.attenuation))
+    }
+}
+
+impl Scatter for Emissive {
+    fn scatter<R: Rng>(&self, _ray: &Ray, _hit: &HitRecord, _rng: &mut R) -> Option<ScatteredRay> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray, _hit: &HitRecord) -> Vec3 {
+        self.radiance
     }
 }
 
-fn random_in_unit_sphere() -> Vec3 {
+impl Scatter for Microfacet {
+    fn scatter<R: Rng>(&self, ray: &Ray, hit: &HitRecord, rng: &mut R) -> Option<ScatteredRay> {
+        let frame = ShadingFrame::new(ray, hit);
+        let wo = frame.to_local(&-ray.direction.normalize());
+        if wo.z <= 0. {
+            return None
+        }
+
+        let wi = if rng.gen::<f32>() < specular_probability(&self.fresnel, &wo) {
+            let h = sample_vndf(&wo, roughness_to_alpha(self.roughness), (rng.gen(), rng.gen()));
+            reflect(&-wo, &h)
+        } else {
+            cosine_hemisphere(rng)
+        };
+
+        let direction = frame.to_world(&wi);
+        let (bsdf, pdf) = self.eval(ray, hit, &direction)?;
+        if pdf <= 0. {
+            return None
+        }
+        Some(ScatteredRay::with_pdf(Ray::with_time(hit.point, direction, ray.time), bsdf / pdf, pdf))
+    }
+
+    // pdf is a mix of specular and diffuse lobes, so scattering agrees with light sampling
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: &Vec3) -> Option<(Vec3, f32)> {
+        let frame = ShadingFrame::new(ray, hit);
+        let wo = frame.to_local(&-ray.direction.normalize());
+        let wi = frame.to_local(&direction.normalize());
+        if wo.z <= 0. || wi.z <= 0. {
+            return Some((Vec3::zeros(), 0.))
+        }
+
+        let alpha = roughness_to_alpha(self.roughness);
+        let h = (wo + wi).normalize();
+        let cos_oh = Vec3::dot(&wo, &h).max(0.);
+        let albedo = albedo(&self.albedo, hit);
+
+        // brdf * cos without fresnel
+        let specular = ggx_d(&h, alpha) * smith_g2(&wo, &wi, alpha) / (4. * wo.z);
+        let specular_pdf = vndf_reflection_pdf(&wo, &h, alpha);
+
+        match self.fresnel {
+            Fresnel::Schlick => Some((fresnel_schlick(cos_oh, &albedo) * specular, specular_pdf)),
+            Fresnel::Conductor { eta, k } => {
+                Some((fresnel_conductor(cos_oh, &eta, &k).component_mul(&albedo) * specular, specular_pdf))
+            },
+            Fresnel::Dielectric { ior } => {
+                // light not reflected by the coat enters and leaves diffuse base
+                let transmitted = (1. - fresnel_dielectric(wo.z, ior)) * (1. - fresnel_dielectric(wi.z, ior));
+                let diffuse = albedo * transmitted * wi.z / PI;
+
+                let p = specular_probability(&self.fresnel, &wo);
+                let bsdf = Vec3::repeat(fresnel_dielectric(cos_oh, ior) * specular) + diffuse;
+                Some((bsdf, p * specular_pdf + (1. - p) * wi.z / PI))
+            },
+        }
+    }
+}
+
+fn specular_probability(fresnel: &Fresnel, wo: &Vec3) -> f32 {
+    match fresnel {
+        Fresnel::Dielectric { ior } => fresnel_dielectric(wo.z, *ior).max(0.1).min(0.9),
+        _ => 1.,
+    }
+}
+
+// local frame of shading normal facing the ray, opaque surfaces are two sided
+struct ShadingFrame {
+    t: Vec3,
+    b: Vec3,
+    n: Vec3,
+}
+
+impl ShadingFrame {
+    fn new(ray: &Ray, hit: &HitRecord) -> ShadingFrame {
+        let n = if Vec3::dot(&ray.direction, &hit.normal) > 0. { -hit.normal } else { hit.normal };
+        let (t, b) = orthonormal_basis(&n);
+        ShadingFrame { t, b, n }
+    }
+
+    fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(v, &self.t), Vec3::dot(v, &self.b), Vec3::dot(v, &self.n))
+    }
+
+    fn to_world(&self, v: &Vec3) -> Vec3 {
+        v.x * self.t + v.y * self.b + v.z * self.n
+    }
+}
+
+// texture at hit point, modulated by vertex colors of meshes
+fn albedo(texture: &Texture, hit: &HitRecord) -> Vec3 {
+    let albedo = texture.value(&hit.uv, &hit.point);
+    hit.vertex_color.map_or(albedo, |color| albedo.component_mul(&color))
+}
+
+fn random_in_unit_sphere<R: Rng>(rng: &mut R) -> Vec3 {
     let sphere = UnitSphereSurface::new();
-    let [x, y, z] = sphere.sample(&mut rand::thread_rng());
+    let [x, y, z] = sphere.sample(rng);
     [x as f32, y as f32, z as f32].into()
 }
 
@@ -111,4 +268,70 @@ mod tests {
         assert_eq!(-v, reflect(&v, &Vec3::new(1., 0., 0.)));
         assert_eq!(v, reflect(&v, &Vec3::new(0., 1., 0.)));
     }
+
+    #[test]
+    fn test_emissive() {
+        let material: Material = Emissive::new(Vec3::new(4., 2., 1.)).into();
+        let ray = Ray::new(Vec3::new(0., 0., 1.), -Vec3::z());
+        let hit = HitRecord::new(1., Vec3::zeros(), Vec3::z(), &material);
+        let mut rng = crate::sampler::sample_rng(0, 0, 0);
+
+        assert!(material.scatter(&ray, &hit, &mut rng).is_none());
+        assert_eq!(material.emitted(&ray, &hit), Vec3::new(4., 2., 1.));
+        assert!(material.eval(&ray, &hit, &Vec3::z()).is_none());
+    }
+
+    // mean weight of scattered rays and reflectance integrated from eval, both under uniform white light
+    fn furnace(material: &Material, ray: &Ray) -> (f32, f32) {
+        let hit = HitRecord::new(1., Vec3::zeros(), Vec3::z(), material);
+        let mut rng = crate::sampler::sample_rng(0, 0, 0);
+        let n = 20_000;
+
+        let sampled: f32 = (0..n)
+            .filter_map(|_| material.scatter(ray, &hit, &mut rng))
+            .map(|scattered| scattered.attenuation.x)
+            .sum();
+
+        let integrated: f32 = (0..n)
+            .map(|_| {
+                let z: f32 = rng.gen();
+                let phi = 2. * PI * rng.gen::<f32>();
+                let r = (1. - z * z).sqrt();
+                Vec3::new(r * phi.cos(), r * phi.sin(), z)
+            })
+            .map(|wi| material.eval(ray, &hit, &wi).unwrap().0.x * 2. * PI)
+            .sum();
+
+        (sampled / n as f32, integrated / n as f32)
+    }
+
+    #[test]
+    fn test_microfacet_energy() {
+        let ray = Ray::new(Vec3::new(-1., 0., 1.), Vec3::new(1., 0., -1.));
+        for &roughness in &[0.3, 0.6, 1.] {
+            let materials: [Material; 2] = [Microfacet::metal(Vec3::repeat(1.), roughness).into(),
+                                            Microfacet::dielectric(Vec3::repeat(1.), roughness, 1.5).into()];
+            for material in &materials {
+                let (sampled, integrated) = furnace(material, &ray);
+                assert!(sampled <= 1., "{} {}", roughness, sampled);
+                assert!(relative_eq!(sampled, integrated, max_relative = 0.05), "{} {} {}", roughness, sampled, integrated);
+            }
+        }
+    }
+
+    #[test]
+    fn test_microfacet_pdf_matches_eval() {
+        let material: Material = Microfacet::dielectric(Vec3::new(0.8, 0.2, 0.2), 0.4, 1.5).into();
+        let ray = Ray::new(Vec3::new(-1., 0.5, 1.), Vec3::new(1., -0.5, -1.));
+        let hit = HitRecord::new(1., Vec3::zeros(), Vec3::z(), &material);
+        let mut rng = crate::sampler::sample_rng(1, 0, 0);
+
+        for _ in 0..64 {
+            if let Some(scattered) = material.scatter(&ray, &hit, &mut rng) {
+                let (bsdf, pdf) = material.eval(&ray, &hit, &scattered.ray.direction).unwrap();
+                assert!(relative_eq!(scattered.pdf.unwrap(), pdf, max_relative = 1e-4));
+                assert!((scattered.attenuation - bsdf / pdf).norm() < 1e-4);
+            }
+        }
+    }
 }
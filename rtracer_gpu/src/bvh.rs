@@ -1,5 +1,5 @@
 use rtracer_core::prelude::*;
-use rand::Rng;
+use rtracer_core::bvh::{BvhBuilder, BvhBuildNode};
 
 #[derive(Clone, Copy)]
 pub struct BvhItem {
@@ -7,6 +7,12 @@ pub struct BvhItem {
     pub id: u32,
 }
 
+impl Bounded for BvhItem {
+    fn aabb(&self) -> Aabb {
+        self.aabb
+    }
+}
+
 pub enum BvhNodeData {
     Leaf(BvhItem),
     Node {
@@ -14,6 +20,8 @@ pub enum BvhNodeData {
     },
 }
 
+// nodes are stored in depth first order, so left child is always next node
+// and escape points to the node to continue with when subtree is skipped
 pub struct BvhNode {
     data: BvhNodeData,
     escape: u32,
@@ -21,27 +29,33 @@ pub struct BvhNode {
 }
 
 impl BvhNode {
-    pub fn build(nodes: &mut Vec<BvhNode>, objs: &mut [BvhItem], escape: u32) -> u32 {
-        let sort_axis = rand::thread_rng().gen_range(0, 3);
-        objs.sort_by(|a, b| a.aabb.min[sort_axis].partial_cmp(&b.aabb.min[sort_axis]).unwrap());
-
-        let n = objs.len();
-        assert!(n != 0, "cant build bvh from zero objects");
-
-        if n == 1 {
-            nodes.push(BvhNode { data: BvhNodeData::Leaf(objs[0]), aabb: objs[0].aabb, escape });
-        } else {
-            let (mut l_objs, mut r_objs) = objs.split_at_mut(n / 2);
-
-            let right = BvhNode::build(nodes, &mut r_objs, escape);
-            let left = BvhNode::build(nodes, &mut l_objs, right);
-
-            let aabb = Aabb::union(&nodes[left as usize].aabb, &nodes[right as usize].aabb);
-
-            let data = BvhNodeData::Node { left };
-            nodes.push(BvhNode { data, aabb, escape });
+    fn build(nodes: &mut Vec<BvhNode>, node: &BvhBuildNode, objs: &[BvhItem], escape: u32) {
+        match node {
+            BvhBuildNode::Leaf { objs: range, .. } => {
+                // every object of the leaf gets its own node, chained by escape
+                let first = nodes.len() as u32;
+                for (i, item) in objs[range.clone()].iter().enumerate() {
+                    let next = if i + 1 == range.len() { escape } else { first + i as u32 + 1 };
+                    nodes.push(BvhNode { data: BvhNodeData::Leaf(*item), aabb: item.aabb, escape: next });
+                }
+            },
+            BvhBuildNode::Node { aabb, left, right } => {
+                let idx = nodes.len();
+                nodes.push(BvhNode { data: BvhNodeData::Node { left: idx as u32 + 1 }, aabb: *aabb, escape });
+
+                let right_idx = (idx + 1 + subtree_nodes_count(left)) as u32;
+
+                BvhNode::build(nodes, left, objs, right_idx);
+                BvhNode::build(nodes, right, objs, escape);
+            },
         }
-        (nodes.len() - 1) as u32
+    }
+}
+
+fn subtree_nodes_count(node: &BvhBuildNode) -> usize {
+    match node {
+        BvhBuildNode::Leaf { objs, .. } => objs.len(),
+        BvhBuildNode::Node { left, right, .. } => 1 + subtree_nodes_count(left) + subtree_nodes_count(right),
     }
 }
 
@@ -51,29 +65,27 @@ pub struct Bvh {
 
 impl Bvh {
     pub fn build(objs: &mut [BvhItem]) -> Bvh {
+        Bvh::build_with(&BvhBuilder::default(), objs)
+    }
+
+    pub fn build_with(builder: &BvhBuilder, objs: &mut [BvhItem]) -> Bvh {
+        let root = builder.build(objs);
+
         let mut nodes = vec![];
-        BvhNode::build(&mut nodes, objs, std::u32::MAX);
+        BvhNode::build(&mut nodes, &root, objs, std::u32::MAX);
 
         Bvh { nodes }
     }
 
+    pub fn nodes_count(&self) -> usize {
+        self.nodes.len()
+    }
+
     pub fn to_gpu(&self) -> Vec<f32> {
         let mut data = vec![];
 
-        let mut cur_node = 0;
-        while cur_node != std::u32::MAX as usize {
-            let node = &self.nodes[cur_node];
-
+        for node in &self.nodes {
             data.extend(&node_to_gpu(node));
-
-            match node.data {
-                BvhNodeData::Leaf(item)=> {
-                    cur_node = node.escape as usize;
-                },
-                BvhNodeData::Node { left } => {
-                    cur_node = left as usize;
-                },
-            }
         }
 
         data
@@ -85,7 +97,7 @@ fn node_to_gpu(node: &BvhNode) -> [f32; 8] {
         BvhNodeData::Leaf(item) => {
             [
                 0., 0., 0., (std::u32::MAX as f32),
-                0., 0., 0., item.id as f32,
+                item.id as f32, 0., 0., node.escape as f32,
             ]
         },
         BvhNodeData::Node { left } => {
@@ -97,4 +109,3 @@ fn node_to_gpu(node: &BvhNode) -> [f32; 8] {
         },
     }
 }
-
@@ -27,6 +27,7 @@ pub struct Renderer {
     device: Arc<Device>,
     queue: Arc<Queue>,
     compute_pipeline: Arc<dyn ComputePipelineAbstract + Send + Sync>,
+    environment_buffer: Arc<CpuAccessibleBuffer<[[f32; 4]]>>,
 }
 
 impl Renderer {
@@ -36,7 +37,13 @@ impl Renderer {
             ComputePipeline::new(device.clone(), &shader.main_entry_point(), &()).unwrap()
         });
 
-        Renderer { device, queue, compute_pipeline }
+        let environment_buffer = environment_to_gpu_buf(device.clone(), &Background::default());
+        Renderer { device, queue, compute_pipeline, environment_buffer }
+    }
+
+    // environment map is drawn without importance sampling
+    pub fn set_background(&mut self, background: &Background) {
+        self.environment_buffer = environment_to_gpu_buf(self.device.clone(), background);
     }
 
     pub fn create_texture(device: Arc<Device>, queue: Arc<Queue>, (width, height): (u32, u32)) -> Arc<dyn ImageViewAccess + Sync + Send> {
@@ -67,9 +74,11 @@ impl Renderer {
             .add_buffer(bvh_node_buffer).unwrap()
             .add_buffer(materials_buffer).unwrap()
             .add_buffer(objects_buffer).unwrap()
+            .add_buffer(self.environment_buffer.clone()).unwrap()
             .build().unwrap()
         );
 
+        // !todo: motion blur, shutter and motion are ignored, scene is drawn at time 0
         let raycast_camera = RaycastCamera::from_camera(camera);
 
         let camera_push_constant = cs::ty::PushConstant {
@@ -79,6 +88,12 @@ impl Renderer {
             vertical: raycast_camera.vertical.into(),
             seed: rand::thread_rng().gen(),
             objects_count: scene.objects_count() as u32,
+            lens_radius: raycast_camera.lens_radius,
+            projection: projection_to_gpu(&raycast_camera.projection),
+            fisheye_fov: match raycast_camera.projection {
+                Projection::Fisheye { fov } => fov.to_radians(),
+                _ => 0.,
+            },
             _dummy0: [1, 1, 1, 1],
             _dummy1: [1, 1, 1, 1],
             _dummy2: [1, 1, 1, 1],
@@ -105,6 +120,37 @@ impl Renderer {
     }
 }
 
+fn projection_to_gpu(projection: &Projection) -> u32 {
+    match projection {
+        Projection::Perspective => 0,
+        Projection::Orthographic { .. } => 1,
+        Projection::Fisheye { .. } => 2,
+        Projection::Equirectangular => 3,
+    }
+}
+
+fn environment_to_gpu_buf(device: Arc<Device>, background: &Background) -> Arc<CpuAccessibleBuffer<[[f32; 4]]>> {
+    let mut buf = vec![];
+
+    match background {
+        Background::Color(color) => {
+            buf.push([1., 1., 0., 1.]);
+            buf.push([color.x, color.y, color.z, 0.]);
+        },
+        Background::Environment(map) => environment_map_to_gpu(&mut buf, map),
+        // !todo: sun disk is not drawn
+        Background::Sky(sky) => environment_map_to_gpu(&mut buf, sky.environment_map()),
+    }
+
+    CpuAccessibleBuffer::from_iter(device, BufferUsage::all(), buf.into_iter()).unwrap()
+}
+
+fn environment_map_to_gpu(buf: &mut Vec<[f32; 4]>, map: &EnvironmentMap) {
+    let image = map.image();
+    buf.push([image.width() as f32, image.height() as f32, map.rotation().to_radians(), map.intensity()]);
+    buf.extend(image.buf().iter().map(|c| [c.x, c.y, c.z, 0.]));
+}
+
 fn objects_to_gpu_buf<'a>(os: impl ExactSizeIterator<Item=(&'a ObjectId, &'a SceneObject)>) -> Vec<[u32; 2]> {
     // !todo: tmp
     let n = os.len();
@@ -191,6 +237,11 @@ fn material_to_gpu(m: &Material) -> [f32; 8] {
         Material::Metal(m) => {
             metal_to_gpu(m)
         },
+        // !todo: microfacet shading on gpu, approximated by closest material
+        Material::Microfacet(m) => match m.fresnel {
+            Fresnel::Dielectric { .. } => lambertian_to_gpu(&Lambertian { albedo: m.albedo.clone() }),
+            _ => metal_to_gpu(&Metal { albedo: m.albedo.clone(), fuzz: m.roughness }),
+        },
         _ => panic!("unsupported material")
     }
 }
@@ -199,16 +250,19 @@ const LAMBERTIAN: u32 = 1;
 const METAL: u32 = 2;
 const DIELECTRIC: u32 = 3;
 
+// !todo: textures are not uploaded, average color is used
 fn lambertian_to_gpu(l: &Lambertian) -> [f32; 8] {
+    let albedo = l.albedo.average();
     [
-        l.albedo[0], l.albedo[1], l.albedo[2], 0.,
+        albedo[0], albedo[1], albedo[2], 0.,
         0., 0., 0., LAMBERTIAN as f32,
     ]
 }
 
 fn metal_to_gpu(m: &Metal) -> [f32; 8] {
+    let albedo = m.albedo.average();
     [
-        m.albedo[0], m.albedo[1], m.albedo[2], m.fuzz,
+        albedo[0], albedo[1], albedo[2], m.fuzz,
         0., 0., 0., METAL as f32,
     ]
 }
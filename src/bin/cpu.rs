@@ -2,21 +2,42 @@ extern crate rand;
 
 extern crate rtracer_core;
 
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::{App, Arg, ArgMatches};
+
 use rtracer_core::prelude::*;
-use rtracer_core::image::{Image};
+use rtracer_core::image::{Image, ImageFormat, ImageError};
+use rtracer_core::film::ToneMapping;
 
 use rtracer_cpu::prelude::*;
 
-use rtracer_cpu::ext_image::{ImageBuffer, Rgba};
-
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 
 const MAX_RAY_DEPTH: u32 = 64;
 const RAYS_FOR_PIXEL: u32 = 32;
+const SEED: u64 = 0;
+
+const BUILTIN_SCENES: [&str; 5] = ["spheres", "dielectric", "triangle", "disk", "cornell_box"];
 
 enum Error {
-    ArgParse,
+    ArgParse(String),
     Io(std::io::Error),
+    Scene(SceneFileError),
+    Image(ImageError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ArgParse(err) => write!(f, "{}", err),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Scene(err) => write!(f, "{}", err),
+            Error::Image(err) => write!(f, "{}", err),
+        }
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -25,32 +46,143 @@ impl From<std::io::Error> for Error {
     }
 }
 
-fn test_scene_dielectric((width, height): (u32, u32)) -> (HitableList<Object>, Camera) {
-    let mut scene = HitableList::new();
+impl From<SceneFileError> for Error {
+    fn from(err: SceneFileError) -> Self {
+        Error::Scene(err)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(err: ImageError) -> Self {
+        Error::Image(err)
+    }
+}
+
+struct Options {
+    scene: Option<PathBuf>,
+    builtin: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    samples: Option<u32>,
+    max_depth: Option<u32>,
+    threads: Option<usize>,
+    seed: Option<u64>,
+    exposure: Option<f32>,
+    tone_mapping: Option<ToneMapping>,
+    aperture: Option<f32>,
+    autofocus: bool,
+    output: PathBuf,
+    format: ImageFormat,
+}
+
+fn parse_value<T: FromStr>(matches: &ArgMatches, name: &str) -> Result<Option<T>, Error> {
+    match matches.value_of(name) {
+        Some(value) => value.parse()
+            .map(Some)
+            .map_err(|_| Error::ArgParse(format!("invalid value '{}' for --{}", value, name))),
+        None => Ok(None),
+    }
+}
+
+// values below 1 give empty or black images
+fn parse_positive(matches: &ArgMatches, name: &str) -> Result<Option<u32>, Error> {
+    match parse_value(matches, name)? {
+        Some(0) => Err(Error::ArgParse(format!("--{} must be at least 1", name))),
+        value => Ok(value),
+    }
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("cpu")
+        .about("Renders scene on cpu")
+        .arg(Arg::with_name("scene")
+            .help("Scene description file (json), settings from it are overridden by options")
+            .index(1))
+        .arg(Arg::with_name("builtin")
+            .long("builtin")
+            .takes_value(true)
+            .possible_values(&BUILTIN_SCENES)
+            .default_value("spheres")
+            .help("Builtin scene to render when no scene file is given"))
+        .arg(Arg::with_name("width").short("W").long("width").takes_value(true).help("Image width"))
+        .arg(Arg::with_name("height").short("H").long("height").takes_value(true).help("Image height"))
+        .arg(Arg::with_name("samples").short("s").long("samples").takes_value(true).help("Rays for pixel"))
+        .arg(Arg::with_name("max-depth").short("d").long("max-depth").takes_value(true).help("Max ray depth"))
+        .arg(Arg::with_name("threads").short("j").long("threads").takes_value(true)
+            .help("Number of render threads, all cores by default"))
+        .arg(Arg::with_name("seed").long("seed").takes_value(true).help("Seed for random numbers"))
+        .arg(Arg::with_name("exposure").short("e").long("exposure").takes_value(true).allow_hyphen_values(true)
+            .help("Exposure compensation in stops, applied to ldr outputs only"))
+        .arg(Arg::with_name("tonemap").long("tonemap").takes_value(true)
+            .possible_values(&["clamp", "reinhard", "aces"])
+            .help("Tone mapping operator, applied to ldr outputs only"))
+        .arg(Arg::with_name("aperture").long("aperture").takes_value(true).help("Lens diameter of scene file camera"))
+        .arg(Arg::with_name("autofocus").long("autofocus")
+            .help("Focus scene file camera on the first surface in the image center"))
+        .arg(Arg::with_name("output").short("o").long("output").takes_value(true)
+            .default_value("outputs/image.ppm")
+            .help("Output image path"))
+        .arg(Arg::with_name("format").short("f").long("format").takes_value(true)
+            .help("Output image format: ppm, png, hdr, pfm or exr, deduced from output extension by default"))
+}
+
+fn parse_args() -> Result<Options, Error> {
+    options_from_matches(&app().get_matches())
+}
+
+fn options_from_matches(matches: &ArgMatches) -> Result<Options, Error> {
+    let output = PathBuf::from(matches.value_of("output").unwrap());
+    let format = match matches.value_of("format") {
+        Some(name) => ImageFormat::from_extension(name)
+            .ok_or_else(|| Error::ArgParse(format!("unknown output format '{}'", name)))?,
+        None => ImageFormat::from_path(&output)
+            .ok_or_else(|| Error::ArgParse(format!("cant deduce output format of '{}'", output.display())))?,
+    };
+
+    Ok(Options {
+        scene: matches.value_of("scene").map(PathBuf::from),
+        builtin: matches.value_of("builtin").unwrap().to_string(),
+        width: parse_positive(matches, "width")?,
+        height: parse_positive(matches, "height")?,
+        samples: parse_positive(matches, "samples")?,
+        max_depth: parse_value(matches, "max-depth")?,
+        threads: parse_value(matches, "threads")?,
+        seed: parse_value(matches, "seed")?,
+        exposure: parse_value(matches, "exposure")?,
+        tone_mapping: matches.value_of("tonemap").and_then(ToneMapping::from_name),
+        aperture: parse_value(matches, "aperture")?,
+        autofocus: matches.is_present("autofocus"),
+        output,
+        format,
+    })
+}
+
+fn test_scene_dielectric((width, height): (u32, u32)) -> (Vec<Object>, Camera) {
+    let mut scene = vec![];
 
     let z = -1.2;
     let dist = 1.15;
 
     // right
-    scene.add(Object::new_sphere(Sphere::new(Vec3::new(dist, 0f32, z), 0.5f32),
-                                 Material::Metal(Metal::new(Vec3::new(0.8, 0.6, 0.2), 0.0))));
+    scene.push(Object::new_sphere(Sphere::new(Vec3::new(dist, 0f32, z), 0.5f32),
+                                  Material::Metal(Metal::new(Vec3::new(0.8, 0.6, 0.2), 0.0))));
     // center
-    scene.add(Object::new_sphere(Sphere::new(Vec3::new(0f32, 0f32, z), 0.5f32),
-                                 Material::Lambertian(Lambertian::new(Vec3::new(0.8, 0.3, 0.3)))));
+    scene.push(Object::new_sphere(Sphere::new(Vec3::new(0f32, 0f32, z), 0.5f32),
+                                  Material::Lambertian(Lambertian::new(Vec3::new(0.8, 0.3, 0.3)))));
     // left
-    scene.add(Object::new_sphere(Sphere::new(Vec3::new(-dist, 0f32, z), 0.5f32),
-                                 Material::Dielectric(Dielectric::new(Vec3::identity(), 1.5))));
-    scene.add(Object::new_sphere(Sphere::new(Vec3::new(-dist, 0f32, z), -0.45f32),
-                                 Material::Dielectric(Dielectric::new(Vec3::identity(), 1.5))));
+    scene.push(Object::new_sphere(Sphere::new(Vec3::new(-dist, 0f32, z), 0.5f32),
+                                  Material::Dielectric(Dielectric::new(Vec3::identity(), 1.5))));
+    scene.push(Object::new_sphere(Sphere::new(Vec3::new(-dist, 0f32, z), -0.45f32),
+                                  Material::Dielectric(Dielectric::new(Vec3::identity(), 1.5))));
 
     // flor
-    scene.add(Object::new_plane(Plane::new(-1. * Vec3::y(), Vec3::y()),
-                                Material::Lambertian(Lambertian::new(Vec3::new(0.0, 0.6, 0.0)))));
+    scene.push(Object::new_plane(Plane::new(-1. * Vec3::y(), Vec3::y()),
+                                 Material::Lambertian(Lambertian::new(Vec3::new(0.0, 0.6, 0.0)))));
 //                                               Arc::new(Material::Metal(Metal::new(Vec3::new(0.0, 0.6, 0.0), 0.)))))));
 
     // big cube
-    scene.add(Object::new_cube(Cube::new(Vec3::new(4.0, 0., 0.), 4. * Vec3::identity()),
-                               Material::Metal(Metal::new(Vec3::new(0.37, 0.15, 0.02), 0.))));
+    scene.push(Object::new_cube(Cube::new(Vec3::new(4.0, 0., 0.), 4. * Vec3::identity()),
+                                Material::Metal(Metal::new(Vec3::new(0.37, 0.15, 0.02), 0.))));
 
     let camera = Camera::new(Vec3::new(-2., 0.75, 0.25), -Vec3::z(), Vec3::y(), 90., width as f32 / height as f32);
 
@@ -81,7 +213,41 @@ fn test_scene_disk((width, height): (u32, u32)) -> (HitableList<Object>, Camera)
     (scene, camera)
 }
 
-fn gen_spheres_in_cube(n: usize, size: f32) -> Vec<Object> {
+fn add_quad(scene: &mut HitableList<Object>, center: Vec3, u: Vec3, v: Vec3, material: &Material) {
+    // normal of both triangles is u x v
+    let (p0, p1, p2, p3) = (center - u - v, center + u - v, center + u + v, center - u + v);
+    scene.add(Object::new_triangle(Triangle::new(p0, p1, p2), material.clone()));
+    scene.add(Object::new_triangle(Triangle::new(p0, p2, p3), material.clone()));
+}
+
+fn test_scene_cornell_box((width, height): (u32, u32)) -> (HitableList<Object>, Camera) {
+    let mut scene = HitableList::new();
+
+    let white = Material::Lambertian(Lambertian::new(Vec3::new(0.73, 0.73, 0.73)));
+    let red = Material::Lambertian(Lambertian::new(Vec3::new(0.65, 0.05, 0.05)));
+    let green = Material::Lambertian(Lambertian::new(Vec3::new(0.12, 0.45, 0.15)));
+    let light = Material::Emissive(Emissive::new(Vec3::new(15., 15., 15.)));
+
+    let (x, y, z) = (Vec3::x(), Vec3::y(), Vec3::z());
+
+    // floor, ceiling, back, left, right
+    add_quad(&mut scene, -y, z, x, &white);
+    add_quad(&mut scene, y, x, z, &white);
+    add_quad(&mut scene, -z, x, y, &white);
+    add_quad(&mut scene, -x, y, z, &red);
+    add_quad(&mut scene, x, z, y, &green);
+
+    add_quad(&mut scene, 0.999 * y, 0.25 * x, 0.25 * z, &light);
+
+    scene.add(Object::new_cube(Cube::new(Vec3::new(-0.35, -0.4, -0.3), Vec3::new(0.6, 1.2, 0.6)), white.clone()));
+    scene.add(Object::new_cube(Cube::new(Vec3::new(0.35, -0.7, 0.3), Vec3::new(0.6, 0.6, 0.6)), white));
+
+    let camera = Camera::new(Vec3::new(0., 0., 3.4), Vec3::zeros(), Vec3::y(), 40., width as f32 / height as f32);
+
+    (scene, camera)
+}
+
+fn gen_spheres_in_cube<R: Rng>(n: usize, size: f32, rng: &mut R) -> Vec<Object> {
     let mut objs = vec![];
 
     let radius = size / n as f32 / 3.;
@@ -94,7 +260,7 @@ fn gen_spheres_in_cube(n: usize, size: f32) -> Vec<Object> {
 
                 let center = Vec3::new(x, y, z);
 //                let material = Material::Lambertian(Lambertian::new(Vec3::new(rand::thread_rng().gen(), rand::thread_rng().gen(), rand::thread_rng().gen())));
-                let material = Material::Metal(Metal::new(Vec3::new(rand::thread_rng().gen(), rand::thread_rng().gen(), rand::thread_rng().gen()), 0.));
+                let material = Material::Metal(Metal::new(Vec3::new(rng.gen(), rng.gen(), rng.gen()), 0.));
 
                 objs.push(Object::new_sphere(Sphere::new(center, radius),
                                              material));
@@ -105,7 +271,7 @@ fn gen_spheres_in_cube(n: usize, size: f32) -> Vec<Object> {
     objs
 }
 
-fn gen_random_spheres(n: usize, size: f32) -> Vec<Object> {
+fn gen_random_spheres<R: Rng>(n: usize, size: f32, rng: &mut R) -> Vec<Object> {
     let mut objs = vec![];
 
     {
@@ -115,9 +281,9 @@ fn gen_random_spheres(n: usize, size: f32) -> Vec<Object> {
     }
 
     for i in 0..n {
-        let radius = 1. + rand::thread_rng().gen::<f32>();
-        let center = Vec3::new(rand::thread_rng().gen::<f32>() * size, radius + rand::thread_rng().gen::<f32>() * size / 2., -rand::thread_rng().gen::<f32>() * size);
-        let material = Material::Lambertian(Lambertian::new(Vec3::new(rand::thread_rng().gen(), rand::thread_rng().gen(), rand::thread_rng().gen())));
+        let radius = 1. + rng.gen::<f32>();
+        let center = Vec3::new(rng.gen::<f32>() * size, radius + rng.gen::<f32>() * size / 2., -rng.gen::<f32>() * size);
+        let material = Material::Lambertian(Lambertian::new(Vec3::new(rng.gen(), rng.gen(), rng.gen())));
 
         objs.push(Object::new_sphere(Sphere::new(center, radius),
                                      material));
@@ -127,49 +293,177 @@ fn gen_random_spheres(n: usize, size: f32) -> Vec<Object> {
 }
 
 fn test_scene_with_random_spheres((width, height): (u32, u32), n: usize, size: f32) -> HitableList<Object> {
+    let mut rng = SampleRng::seed_from_u64(SEED);
+
     let mut list = HitableList::new();
-//    gen_random_spheres(n, size).into_iter().for_each(|s| list.add(s));
-    gen_spheres_in_cube(n, size).into_iter().for_each(|s| list.add(s));
+//    gen_random_spheres(n, size, &mut rng).into_iter().for_each(|s| list.add(s));
+    gen_spheres_in_cube(n, size, &mut rng).into_iter().for_each(|s| list.add(s));
 
     list
 }
 
-fn test_scene_with_random_spheres_bvh((width, height): (u32, u32), n: usize, size: f32) -> BvhNode<Object> {
+fn test_scene_with_random_spheres_bvh((width, height): (u32, u32), n: usize, size: f32) -> (BvhNode<Object>, LightList) {
     use std::time::Instant;
+    let mut rng = SampleRng::seed_from_u64(SEED);
+
+    let mut objects = gen_spheres_in_cube(n, size, &mut rng);
+    let lights = LightList::from_objects(objects.iter());
+
     let start = Instant::now();
-    let bvh = BvhNode::build(gen_spheres_in_cube(n, size).as_mut_slice());
+    let bvh = BvhNode::build(&mut objects);
     println!("bhv construct: {} sec", start.elapsed().as_micros() as f64 / 1_000_000.);
 
-    bvh
+    (bvh, lights)
+}
+
+fn apply_options(settings: &mut RenderSettings, options: &Options) {
+    settings.width = options.width.unwrap_or(settings.width);
+    settings.height = options.height.unwrap_or(settings.height);
+    settings.samples = options.samples.unwrap_or(settings.samples);
+    settings.max_depth = options.max_depth.unwrap_or(settings.max_depth);
+    settings.seed = options.seed.unwrap_or(settings.seed);
+    settings.exposure = options.exposure.unwrap_or(settings.exposure);
+    settings.tone_mapping = options.tone_mapping.unwrap_or(settings.tone_mapping);
 }
 
-fn run() {
-    let args: Vec<String> = std::env::args().collect();
+fn render<H: Hit + Sync + Send>(scene: &H, lights: &LightList, camera: &Camera, settings: &RenderSettings, background: Background,
+                                options: &Options) -> Result<(), Error> {
+    let mut img = Image::new(settings.width, settings.height);
+
+    let mut renderer = CPURenderer::new(settings.samples, settings.max_depth);
+    renderer.set_seed(settings.seed);
+    renderer.set_background(background);
+
+    renderer.render(&mut img, camera, scene, lights);
 
-//    let (width, height) = (1920, 1080);
-    let (width, height) = (640, 480);
-    let (width, height) = (200, 100);
-    let mut img = Image::new(width, height);
+    if let Some(dir) = options.output.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    // float formats keep linear radiance for compositing
+    if options.format.is_hdr() {
+        img.save_with_format(&options.output, options.format)?;
+    } else {
+        settings.film().develop(&img).save_with_format(&options.output, options.format)?;
+    }
+
+    Ok(())
+}
 
-    let n = 5;
-    let size = 20f32;
-    let camera = Camera::new(Vec3::new(1., size / 1.5, -1.), Vec3::new(size / 2., 0., size / 2.), Vec3::y(), 90., width as f32 / height as f32);
-//    let scene = test_scene_with_random_spheres((width, height), n, size);
-    let scene = test_scene_with_random_spheres_bvh((width, height), n, size);
-//    let (scene, camera) = test_scene_dielectric((width, height));
-//    let (scene, camera) = test_scene_triangle((width, height));
-//    let (scene, camera) = test_scene_disk((width, height));
+fn render_scene_file(path: &Path, options: &Options) -> Result<(), Error> {
+    let mut file = SceneFile::load(path)?;
 
-    let mut renderer = CPURenderer::new(RAYS_FOR_PIXEL, MAX_RAY_DEPTH);
+    let mut settings = file.settings;
+    apply_options(&mut settings, options);
+    if options.width.is_some() || options.height.is_some() {
+        file.camera.aspect = settings.width as f32 / settings.height as f32;
+    }
+    file.camera.aperture = options.aperture.unwrap_or(file.camera.aperture);
 
-    renderer.render(&mut img, &camera, &scene);
+    let mut objects = file.scene.to_objects();
+    if objects.is_empty() {
+        return Err(Error::Scene(SceneFileError::EmptyScene))
+    }
+    let mut lights = LightList::from_objects(objects.iter());
+    lights.set_background(&file.background);
+    let scene = BvhNode::build(&mut objects);
 
-    img.write_ppm(&mut std::fs::File::create("outputs/image.ppm").unwrap());
+    if options.autofocus && autofocus(&mut file.camera, &scene).is_none() {
+        eprintln!("nothing to focus on in the image center");
+    }
 
-//    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &img.iter()).unwrap();
-//    image.save("image.png").unwrap();
+    render(&scene, &lights, &file.camera, &settings, file.background, options)
+}
+
+fn render_builtin_scene(name: &str, options: &Options) -> Result<(), Error> {
+    let mut settings = RenderSettings { samples: RAYS_FOR_PIXEL, max_depth: MAX_RAY_DEPTH, seed: SEED, ..RenderSettings::default() };
+    apply_options(&mut settings, options);
+
+    let (width, height) = (settings.width, settings.height);
+
+    match name {
+        "spheres" => {
+            let n = 5;
+            let size = 20f32;
+            let camera = Camera::new(Vec3::new(1., size / 1.5, -1.), Vec3::new(size / 2., 0., size / 2.), Vec3::y(), 90., width as f32 / height as f32);
+//            let scene = test_scene_with_random_spheres((width, height), n, size);
+            let (scene, lights) = test_scene_with_random_spheres_bvh((width, height), n, size);
+            render(&scene, &lights, &camera, &settings, settings.background().into(), options)
+        },
+        "dielectric" => {
+            let (mut objects, camera) = test_scene_dielectric((width, height));
+            let lights = LightList::from_objects(objects.iter());
+            let scene = BvhNode::build(&mut objects);
+            render(&scene, &lights, &camera, &settings, settings.background().into(), options)
+        },
+        "triangle" => {
+            let (scene, camera) = test_scene_triangle((width, height));
+            render(&scene, &LightList::from_objects(scene.iter()), &camera, &settings, settings.background().into(), options)
+        },
+        "disk" => {
+            let (scene, camera) = test_scene_disk((width, height));
+            render(&scene, &LightList::from_objects(scene.iter()), &camera, &settings, settings.background().into(), options)
+        },
+        "cornell_box" => {
+            settings.background = [0., 0., 0.];
+            let (scene, camera) = test_scene_cornell_box((width, height));
+            render(&scene, &LightList::from_objects(scene.iter()), &camera, &settings, settings.background().into(), options)
+        },
+        _ => Err(Error::ArgParse(format!("unknown builtin scene '{}'", name))),
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let options = parse_args()?;
+
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|err| Error::ArgParse(format!("cant use {} threads: {}", threads, err)))?;
+    }
+
+    match &options.scene {
+        Some(path) => render_scene_file(path, &options),
+        None => render_builtin_scene(&options.builtin, &options),
+    }
 }
 
 fn main() {
-    run();
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, Error> {
+        let matches = app().get_matches_from_safe(std::iter::once("cpu").chain(args.iter().cloned())).unwrap();
+        options_from_matches(&matches)
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = parse(&["scene.json", "-W", "320", "-s", "8", "-e", "-1.5", "-o", "out.exr"]).ok().unwrap();
+        assert_eq!(options.scene, Some(PathBuf::from("scene.json")));
+        assert_eq!((options.width, options.height, options.samples), (Some(320), None, Some(8)));
+        assert_eq!(options.exposure, Some(-1.5));
+        assert!(options.format.is_hdr());
+
+        assert!(parse(&["-o", "image.unknown"]).is_err());
+        assert!(parse(&["--seed", "many"]).is_err());
+    }
+
+    #[test]
+    fn test_reject_empty_image() {
+        for args in &[["-s", "0"], ["-W", "0"], ["-H", "0"]] {
+            match parse(args) {
+                Err(Error::ArgParse(_)) => {},
+                _ => panic!("{:?} must be rejected", args),
+            }
+        }
+    }
 }
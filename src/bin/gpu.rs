@@ -36,7 +36,7 @@ fn create_scene() -> SceneData {
 
 fn add_triangles_to_scene(scene: &mut SceneData, ts: &[Triangle], material: Material) {
     for t in ts {
-        scene.create_object((*t).into(), material);
+        scene.create_object((*t).into(), material.clone());
     }
 }
 